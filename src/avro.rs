@@ -0,0 +1,233 @@
+//! Avro has no polars sink or lazy scan. Both directions go through a temporary Arrow IPC file,
+//! which the streaming engine can write and scan, converted one Avro block at a time so neither
+//! side holds the whole frame in memory.
+//!
+//! avro-schema, which polars reads and writes Avro with, only knows the Deflate and Snappy
+//! codecs. Zstandard files get their header written and their blocks compressed here.
+use crate::{configs::output::AvroCodecConfig, inputs::TempFile, outputs::OutputError};
+use avro_schema::{
+    file::{Block, CompressedBlock, Compression},
+    schema::{Record, Schema as AvroSchema},
+    write::encode::zigzag_encode,
+};
+use polars::prelude::*;
+use polars_arrow::io::{avro, ipc};
+use polars_io::ipc::{IpcWriter, IpcWriterOptions};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 4] = [b'O', b'b', b'j', 1];
+// the marker avro_schema::write::write_block ends every block with
+const SYNC_MARKER: [u8; 16] = [1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4];
+const ZSTANDARD: &[u8] = b"zstandard";
+
+/// An Avro long, zigzag and variable length encoded.
+fn read_long(reader: &mut impl Read) -> io::Result<i64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            let magnitude = i64::try_from(value >> 1).expect("shifted right, fits in i64");
+            return Ok(if value & 1 == 0 {
+                magnitude
+            } else {
+                !magnitude
+            });
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Avro long is too long",
+    ))
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = usize::try_from(read_long(reader)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "negative Avro length"))?;
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The `avro.codec` of a file header, None when it has none.
+fn read_codec(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    let mut codec = None;
+    loop {
+        let count = read_long(&mut reader)?;
+        if count == 0 {
+            return Ok(codec);
+        }
+        // a negative count is followed by the size of the entries in bytes
+        if count < 0 {
+            read_long(&mut reader)?;
+        }
+        for _ in 0..count.unsigned_abs() {
+            let key = read_bytes(&mut reader)?;
+            let value = read_bytes(&mut reader)?;
+            if key == b"avro.codec" {
+                codec = Some(value);
+            }
+        }
+    }
+}
+
+/// The next block of a Zstandard file, decompressed. None at the end of the file.
+fn read_zstd_block(reader: &mut impl Read, marker: &[u8; 16]) -> PolarsResult<Option<Block>> {
+    let rows = match read_long(reader) {
+        Ok(rows) => rows,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let data = zstd::decode_all(read_bytes(reader)?.as_slice())?;
+    let mut block_marker = [0u8; 16];
+    reader.read_exact(&mut block_marker)?;
+    if &block_marker != marker {
+        polars_bail!(ComputeError: "Avro block does not end with the file's sync marker");
+    }
+    let rows = usize::try_from(rows)
+        .map_err(|_| PolarsError::ComputeError("negative Avro row count".into()))?;
+    Ok(Some(Block::new(rows, data)))
+}
+
+/// Copy the blocks of an Avro file into an Arrow IPC file that can be scanned lazily.
+///
+/// # Errors
+/// When the Avro file cannot be read or decoded, or the IPC file cannot be written.
+pub fn avro_to_ipc(avro_path: &Path, ipc_path: &Path) -> PolarsResult<()> {
+    let avro_error = |e: avro_schema::error::Error| {
+        PolarsError::ComputeError(
+            format!("Error reading avro {} - {e}", avro_path.display()).into(),
+        )
+    };
+    let mut reader = BufReader::new(File::open(avro_path)?);
+    let metadata = avro_schema::read::read_metadata(&mut reader).map_err(avro_error)?;
+    let arrow_schema = avro::read::infer_schema(&metadata.record)?;
+    let schema = Schema::from_arrow_schema(&arrow_schema);
+
+    let mut writer = IpcWriter::new(File::create(ipc_path)?).batched(&schema)?;
+    if read_codec(avro_path)?.as_deref() == Some(ZSTANDARD) {
+        let projection = vec![true; arrow_schema.len()];
+        while let Some(block) = read_zstd_block(&mut reader, &metadata.marker)? {
+            let batch = avro::read::deserialize(
+                &block,
+                &arrow_schema,
+                &metadata.record.fields,
+                &projection,
+            )?;
+            writer.write_batch(&DataFrame::from(batch))?;
+        }
+        return writer.finish();
+    }
+    for batch in avro::read::Reader::new(reader, metadata, arrow_schema.clone(), None) {
+        writer.write_batch(&DataFrame::from(batch?))?;
+    }
+    writer.finish()
+}
+
+/// Stream a frame into an Avro file, one block per record batch of the streaming engine.
+///
+/// # Errors
+/// When the frame cannot be computed or encoded, or `writer` fails.
+pub fn write_avro(
+    df: LazyFrame,
+    writer: &mut impl Write,
+    codec: Option<&AvroCodecConfig>,
+) -> Result<(), OutputError> {
+    let spool = TempFile::new("avro_output.arrow");
+    spool_and_write_avro(df, spool.path(), writer, codec)
+}
+
+fn spool_and_write_avro(
+    df: LazyFrame,
+    spool: &Path,
+    writer: &mut impl Write,
+    codec: Option<&AvroCodecConfig>,
+) -> Result<(), OutputError> {
+    let polars_error = |e: PolarsError| OutputError::Io(format!("Failed to write Avro: {e}"));
+    let avro_error =
+        |e: avro_schema::error::Error| OutputError::Io(format!("Failed to write Avro block: {e}"));
+    df.sink_ipc(
+        SinkTarget::Path(Arc::new(spool.to_path_buf())),
+        IpcWriterOptions {
+            // avro serializers need the classic string and binary layouts, not views
+            compat_level: CompatLevel::oldest(),
+            ..Default::default()
+        },
+        None,
+        SinkOptions::default(),
+    )
+    .and_then(LazyFrame::collect)
+    .map_err(polars_error)?;
+
+    let mut reader = BufReader::new(File::open(spool)?);
+    let metadata = ipc::read::read_file_metadata(&mut reader).map_err(polars_error)?;
+    let record = avro::write::to_record(&metadata.schema, String::new()).map_err(polars_error)?;
+    let compression = match codec {
+        Some(AvroCodecConfig::Deflate) => Some(Compression::Deflate),
+        Some(AvroCodecConfig::Snappy) => Some(Compression::Snappy),
+        Some(AvroCodecConfig::Zstd) | None => None,
+    };
+    if matches!(codec, Some(AvroCodecConfig::Zstd)) {
+        write_zstd_metadata(writer, record.clone())?;
+    } else {
+        avro_schema::write::write_metadata(writer, record.clone(), compression)
+            .map_err(avro_error)?;
+    }
+
+    let mut compressed = CompressedBlock::default();
+    for batch in ipc::read::FileReader::new(reader, metadata, None, None) {
+        let batch = batch.map_err(polars_error)?;
+        let mut serializers = batch
+            .arrays()
+            .iter()
+            .zip(record.fields.iter())
+            .map(|(array, field)| avro::write::new_serializer(array.as_ref(), &field.schema))
+            .collect::<Vec<_>>();
+        let mut block = Block::new(batch.len(), Vec::new());
+        avro::write::serialize(&mut serializers, &mut block);
+        if matches!(codec, Some(AvroCodecConfig::Zstd)) {
+            compressed.number_of_rows = block.number_of_rows;
+            compressed.data = zstd::bulk::compress(&block.data, 0)?;
+        } else {
+            avro_schema::write::compress(&mut block, &mut compressed, compression)
+                .map_err(avro_error)?;
+        }
+        avro_schema::write::write_block(writer, &compressed).map_err(avro_error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// The header `avro_schema::write::write_metadata` writes, with `zstandard` as the codec.
+fn write_zstd_metadata(writer: &mut impl Write, record: Record) -> Result<(), OutputError> {
+    let avro_error =
+        |e: avro_schema::error::Error| OutputError::Io(format!("Failed to write Avro header: {e}"));
+    let schema = serde_json::to_vec(&AvroSchema::Record(record))
+        .map_err(|e| OutputError::Io(format!("Failed to write Avro schema: {e}")))?;
+    writer.write_all(&MAGIC)?;
+    zigzag_encode(2, writer).map_err(avro_error)?;
+    for (key, value) in [
+        (&b"avro.schema"[..], schema.as_slice()),
+        (b"avro.codec", ZSTANDARD),
+    ] {
+        for bytes in [key, value] {
+            zigzag_encode(
+                i64::try_from(bytes.len()).expect("header fits in i64"),
+                writer,
+            )
+            .map_err(avro_error)?;
+            writer.write_all(bytes)?;
+        }
+    }
+    writer.write_all(&[0])?;
+    writer.write_all(&SYNC_MARKER)?;
+    Ok(())
+}
@@ -1,18 +1,38 @@
-use crate::configs::{input::InputConfig, output::OutputConfig, schema::Schema};
+use crate::configs::{
+    input::InputConfig,
+    output::OutputConfig,
+    schema::{Schema, SchemaDtype},
+};
 use polars::prelude::{
     col, lit, when, DataType, Expr, QuantileMethod, RollingOptionsFixedWindow, RoundMode, NULL,
 };
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub input: Option<InputConfig>,
+    // additional sources that operations such as Join can reference by name
+    #[serde(default)]
+    pub inputs: HashMap<String, InputConfig>,
+    // named sub pipelines over one of the inputs, referenced like an input
+    #[serde(default)]
+    pub pipelines: HashMap<String, PipelineConfig>,
+    #[serde(default)]
     pub operations: Vec<Operation>,
     pub output_schema: Option<Schema>,
     pub outputs: Option<Vec<OutputConfig>>,
 }
 
 #[derive(Deserialize, Debug)]
+pub struct PipelineConfig {
+    // name of an entry in `inputs` or another pipeline
+    pub input: String,
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub enum AllowedFilterCondition {
     EQ,
     EQMISSING,
@@ -25,7 +45,7 @@ pub enum AllowedFilterCondition {
     ISNOTNULL,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum Operation {
     Filter {
@@ -59,12 +79,28 @@ pub enum Operation {
         order: String,
         limit: Option<u32>,
     },
-    // support more then one input..
+    // keep the first rows
+    Limit {
+        rows: u32,
+    },
     SelfJoin {
         left_on: Vec<String>,
         right_on: Vec<String>,
         how: JoinType,
     },
+    Join {
+        right: String, // name of an entry in `inputs` or `pipelines`
+        left_on: Vec<String>,
+        right_on: Vec<String>,
+        how: JoinType,
+        #[serde(default)]
+        suffix: Option<String>,
+    },
+    Concat {
+        inputs: Vec<String>, // names of entries in `inputs` or `pipelines`
+        #[serde(default)]
+        how: ConcatMode,
+    },
 
     WithColumn {
         name: Option<String>,
@@ -95,6 +131,83 @@ pub enum Operation {
         #[serde(default)]
         aggregate_function: Option<AllowedGroupFunction>,
     },
+    Unpivot {
+        #[serde(default)]
+        index: Vec<ColumnSelector>,
+        #[serde(default)]
+        on: Vec<ColumnSelector>, // empty means every column not in index
+        #[serde(default)]
+        variable_name: Option<String>,
+        #[serde(default)]
+        value_name: Option<String>,
+    },
+}
+
+/// Picks columns by exact name, by a `^...$` regex, or by dtype.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ColumnSelector {
+    Name(String),
+    Dtype { dtype: SchemaDtype },
+}
+impl ColumnSelector {
+    /// Names of the columns of `schema` the selector picks, in schema order.
+    ///
+    /// # Errors
+    /// When the pattern is not a valid regex or the selector matches no column.
+    pub fn resolve(&self, schema: &polars::prelude::Schema) -> Result<Vec<String>, String> {
+        let names = schema.iter_names().map(|name| name.as_str().to_owned());
+        // an empty selection would make unpivot fall back to every column
+        match self {
+            ColumnSelector::Name(name) if name.starts_with('^') && name.ends_with('$') => {
+                let pattern = regex::Regex::new(name)
+                    .map_err(|e| format!("Invalid column pattern '{name}' - {e}"))?;
+                let columns: Vec<String> =
+                    names.filter(|column| pattern.is_match(column)).collect();
+                if columns.is_empty() {
+                    return Err(format!("Column pattern '{name}' matches no columns"));
+                }
+                Ok(columns)
+            }
+            ColumnSelector::Name(name) => {
+                if schema.contains(name) {
+                    Ok(vec![name.clone()])
+                } else {
+                    Err(format!("Column '{name}' not found"))
+                }
+            }
+            ColumnSelector::Dtype { dtype } => {
+                let columns: Vec<String> = schema
+                    .iter()
+                    .filter(|(_, column_dtype)| dtype.matches(column_dtype))
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                if columns.is_empty() {
+                    return Err(format!("Dtype selector {dtype:?} matches no columns"));
+                }
+                Ok(columns)
+            }
+        }
+    }
+
+    /// Columns picked by any of `selectors`, without duplicates.
+    ///
+    /// # Errors
+    /// When one of the selectors fails to resolve.
+    pub fn resolve_all(
+        selectors: &[ColumnSelector],
+        schema: &polars::prelude::Schema,
+    ) -> Result<Vec<String>, String> {
+        let mut columns: Vec<String> = Vec::new();
+        for selector in selectors {
+            for column in selector.resolve(schema)? {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        Ok(columns)
+    }
 }
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -174,7 +287,7 @@ pub struct ColumnRename {
     pub old_name: String,
     pub new_name: String,
 }
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)] // Important! Allows serde to try each variant
 pub enum FilterField {
     SingleNumber(i64),
@@ -195,7 +308,7 @@ pub enum TimeUnitPrecision {
     Milliseconds,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub enum TimeUnit {
     Seconds,
     Minutes,
@@ -254,7 +367,16 @@ impl Into<polars::prelude::JoinType> for &JoinType {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
+pub enum ConcatMode {
+    #[default]
+    Vertical,
+    // union by column name, columns missing from a frame are filled with nulls
+    Diagonal,
+    Horizontal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Aggregate {
     pub column: String,
     pub alias: Option<String>,
@@ -277,7 +399,7 @@ pub enum AllowedGroupFunction {
     PERCENTILE(f64),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum Expression {
     Column {
@@ -301,7 +423,7 @@ pub enum Expression {
     },
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub enum ExpressionFunction {
     PERCENTILE {
         column: String,
@@ -401,7 +523,7 @@ pub enum ExpressionFunction {
         pattern: String,
     },
 }
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub enum ExpressionOperation {
     ADD,
     SUBTRACT,
@@ -422,24 +544,7 @@ pub enum ExpressionOperation {
 impl Aggregate {
     pub fn to_polars_expr(&self) -> Result<polars::prelude::Expr, String> {
         dbg!(&self);
-        let col = col(&self.column);
-
-        let col = match self.function.clone() {
-            AllowedGroupFunction::MIN => col.min(),
-            AllowedGroupFunction::MAX => col.max(),
-            AllowedGroupFunction::SUM => col.sum(),
-            AllowedGroupFunction::MEAN => col.mean(),
-            AllowedGroupFunction::MEDIAN => col.median(),
-            AllowedGroupFunction::STD(ddof) => col.std(ddof),
-            AllowedGroupFunction::VAR(ddof) => col.var(ddof),
-            AllowedGroupFunction::COUNT => col.count(),
-            AllowedGroupFunction::FIRST => col.first(),
-            AllowedGroupFunction::LAST => col.last(),
-            AllowedGroupFunction::NUNIQUE => col.n_unique(),
-            AllowedGroupFunction::PERCENTILE(percentile) => {
-                col.quantile(lit(percentile), QuantileMethod::Nearest)
-            }
-        };
+        let col = self.function.apply(col(&self.column));
 
         let col = if let Some(alias) = &self.alias {
             col.alias(alias)
@@ -450,6 +555,33 @@ impl Aggregate {
     }
 }
 
+impl AllowedGroupFunction {
+    pub fn apply(&self, expr: Expr) -> Expr {
+        match self {
+            AllowedGroupFunction::MIN => expr.min(),
+            AllowedGroupFunction::MAX => expr.max(),
+            AllowedGroupFunction::SUM => expr.sum(),
+            AllowedGroupFunction::MEAN => expr.mean(),
+            AllowedGroupFunction::MEDIAN => expr.median(),
+            AllowedGroupFunction::STD(ddof) => expr.std(*ddof),
+            AllowedGroupFunction::VAR(ddof) => expr.var(*ddof),
+            AllowedGroupFunction::COUNT => expr.count(),
+            AllowedGroupFunction::FIRST => expr.first(),
+            AllowedGroupFunction::LAST => expr.last(),
+            AllowedGroupFunction::NUNIQUE => expr.n_unique(),
+            AllowedGroupFunction::PERCENTILE(percentile) => {
+                expr.quantile(lit(*percentile), QuantileMethod::Nearest)
+            }
+        }
+    }
+
+    // pivot aggregations can only reference the values being pivoted, which
+    // polars exposes as the unnamed element column.
+    pub fn to_pivot_expr(&self) -> Expr {
+        self.apply(col(""))
+    }
+}
+
 impl FilterField {
     pub fn to_polars_expr(&self) -> Result<polars::prelude::Expr, String> {
         match self {
@@ -0,0 +1,204 @@
+use polars_io::cloud::CloudOptions;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Credentials and connection settings for object storage. Secrets are never written in the
+/// config, the `*_env` fields name the environment variables holding them.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CloudConfig {
+    #[serde(default)]
+    pub region: Option<String>,
+    // e.g. http://localhost:9000 for a local MinIO
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    // needed for plain http endpoints
+    #[serde(default)]
+    pub allow_http: bool,
+    // s3 access key id, or the storage account name for azure
+    #[serde(default)]
+    pub access_key_id_env: Option<String>,
+    // s3 secret access key, or the storage account key for azure
+    #[serde(default)]
+    pub secret_access_key_env: Option<String>,
+    #[serde(default)]
+    pub session_token_env: Option<String>,
+    // gcs service account json file
+    #[serde(default)]
+    pub service_account_path: Option<String>,
+    // aws profile from the shared credentials and config files
+    #[serde(default)]
+    pub profile: Option<String>,
+    // read public data without signing requests
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(default)]
+    pub max_retries: Option<usize>,
+    // per request timeout
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    // any other object_store setting, e.g. { aws_virtual_hosted_style_request = "false" }
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+}
+
+fn env_value(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("Environment variable {name} is not set"))
+}
+
+/// Keys of one section of an aws credentials or config file. Profiles other than default are
+/// written `[profile name]` in the config file but `[name]` in the credentials file.
+fn aws_profile_section(path: &Path, profile: &str) -> HashMap<String, String> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let mut section = HashMap::new();
+    let mut in_profile = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with(';') || line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.trim();
+            in_profile = name == profile || name.strip_prefix("profile ") == Some(profile);
+            continue;
+        }
+        if let (true, Some((key, value))) = (in_profile, line.split_once('=')) {
+            section.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    section
+}
+
+fn aws_file(env: &str, name: &str) -> Option<PathBuf> {
+    std::env::var_os(env).map(PathBuf::from).or_else(|| {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws").join(name))
+    })
+}
+
+enum Provider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+fn provider(url: &str) -> Result<Provider, String> {
+    match url.split("://").next().unwrap_or_default() {
+        "s3" | "s3a" => Ok(Provider::Aws),
+        "gs" | "gcs" => Ok(Provider::Gcp),
+        "az" | "azure" | "abfs" | "abfss" | "adl" => Ok(Provider::Azure),
+        scheme => Err(format!("Unsupported cloud url scheme {scheme}:// in {url}")),
+    }
+}
+
+impl CloudConfig {
+    /// Polars cloud options for `url`, using the setting names of the url's provider.
+    ///
+    /// # Errors
+    /// When the url's scheme is not a supported cloud provider.
+    pub fn to_cloud_options(&self, url: &str) -> Result<CloudOptions, String> {
+        let provider = provider(url)?;
+        let mut config: Vec<(String, String)> = self
+            .options
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let mut set = |key: &str, value: String| config.push((key.to_string(), value));
+        if self.allow_http {
+            set("allow_http", "true".to_string());
+        }
+        if let Some(timeout) = self.timeout_secs {
+            set("timeout", format!("{timeout}s"));
+        }
+        if let Some(timeout) = self.connect_timeout_secs {
+            set("connect_timeout", format!("{timeout}s"));
+        }
+        match provider {
+            Provider::Aws => {
+                let mut profile = HashMap::new();
+                if let Some(name) = &self.profile {
+                    if let Some(path) = aws_file("AWS_CONFIG_FILE", "config") {
+                        profile.extend(aws_profile_section(&path, name));
+                    }
+                    if let Some(path) = aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials") {
+                        profile.extend(aws_profile_section(&path, name));
+                    }
+                    if profile.is_empty() {
+                        return Err(format!("aws profile {name} was not found"));
+                    }
+                }
+                // explicit settings win over the profile's
+                if let Some(region) = self.region.as_ref().or(profile.get("region")) {
+                    set("aws_region", region.clone());
+                }
+                for key in [
+                    "aws_access_key_id",
+                    "aws_secret_access_key",
+                    "aws_session_token",
+                ] {
+                    if let Some(value) = profile.get(key) {
+                        set(key, value.clone());
+                    }
+                }
+                if self.anonymous {
+                    set("aws_skip_signature", "true".to_string());
+                }
+                if let Some(endpoint_url) = &self.endpoint_url {
+                    set("aws_endpoint_url", endpoint_url.clone());
+                }
+                if let Some(name) = &self.access_key_id_env {
+                    set("aws_access_key_id", env_value(name)?);
+                }
+                if let Some(name) = &self.secret_access_key_env {
+                    set("aws_secret_access_key", env_value(name)?);
+                }
+                if let Some(name) = &self.session_token_env {
+                    set("aws_session_token", env_value(name)?);
+                }
+            }
+            Provider::Gcp => {
+                if let Some(path) = &self.service_account_path {
+                    set("google_service_account_path", path.clone());
+                }
+                if self.anonymous {
+                    set("google_skip_signature", "true".to_string());
+                }
+                if self.endpoint_url.is_some() || self.access_key_id_env.is_some() {
+                    return Err(
+                        "gcs takes service_account_path, not endpoint_url or access keys".into(),
+                    );
+                }
+            }
+            Provider::Azure => {
+                if self.anonymous {
+                    set("azure_skip_signature", "true".to_string());
+                }
+                if let Some(endpoint_url) = &self.endpoint_url {
+                    set("azure_storage_endpoint", endpoint_url.clone());
+                }
+                if let Some(name) = &self.access_key_id_env {
+                    set("azure_storage_account_name", env_value(name)?);
+                }
+                if let Some(name) = &self.secret_access_key_env {
+                    set("azure_storage_account_key", env_value(name)?);
+                }
+            }
+        }
+        if self.profile.is_some() && !matches!(provider, Provider::Aws) {
+            return Err("profile is only supported for s3 locations".into());
+        }
+        let mut cloud_options = CloudOptions::from_untyped_config(url, config)
+            .map_err(|e| format!("Invalid cloud options for {url} - {e}"))?;
+        if let Some(max_retries) = self.max_retries {
+            cloud_options.max_retries = max_retries;
+        }
+        Ok(cloud_options)
+    }
+}
@@ -1,16 +1,39 @@
 use serde::Deserialize;
 
-use super::schema::Schema;
+use super::{
+    cloud::CloudConfig,
+    schema::{Schema, SchemaDtype},
+};
+use std::collections::HashMap;
 #[derive(Deserialize, Debug)]
 pub struct InputConfig {
     // can be a file or a cloud url
     pub location: String,
     #[serde(flatten)]
     pub format: InputFormat,
+    // name of a column recording the file each row was read from
+    #[serde(default)]
+    pub include_file_paths: Option<String>,
+    #[serde(default)]
+    pub compression: InputCompression,
+    // how records from a tcp:// or udp:// listener are grouped before processing
+    #[serde(default)]
+    pub batch: BatchConfig,
+    // credentials and settings for cloud locations
+    #[serde(default)]
+    pub cloud: CloudConfig,
     //#[serde(default)]
     //pub schema: Option<Schema>,
 }
+/// Location that makes the input read from standard input.
+pub const STDIN_LOCATION: &str = "-";
+
 impl InputConfig {
+    #[must_use]
+    pub fn is_stdin(&self) -> bool {
+        self.location == STDIN_LOCATION
+    }
+
     pub fn new(location: &str, format: &str, is_cloud: bool, skip_sample: bool) -> Self {
         let input_format = match format {
             "csv" => InputFormat::Csv {
@@ -18,11 +41,26 @@ impl InputConfig {
                 delimiter: ",".to_string(),
                 has_header: true,
                 schema: None,
+                quote_char: default_quote_char(),
+                comment_prefix: None,
+                skip_rows: 0,
+                null_values: Vec::new(),
+                infer_schema_length: None,
+                try_parse_dates: false,
+                truncate_ragged_lines: false,
+                encoding: CsvEncodingConfig::Utf8,
+            },
+            "json" => InputFormat::Json {
+                records_path: None,
+                schema: None,
             },
-            "json" => InputFormat::Json,
             "parquet" => InputFormat::Parquet,
             "ipc" => InputFormat::Ipc,
             "avro" => InputFormat::Avro,
+            "nginx" => InputFormat::NginxCombined,
+            "apache" => InputFormat::ApacheCommon,
+            "logfmt" => InputFormat::Logfmt,
+            "syslog" => InputFormat::Syslog,
             "jsonl" => InputFormat::JsonLines {
                 is_cloud,
                 skip_sample,
@@ -32,9 +70,50 @@ impl InputConfig {
         Self {
             location: location.to_owned(),
             format: input_format,
+            include_file_paths: None,
+            compression: InputCompression::Auto,
+            batch: BatchConfig::default(),
+            cloud: CloudConfig::default(),
         }
     }
 }
+/// Records that arrive over time are processed once `max_records` have arrived or
+/// `max_wait_ms` has passed since the first one of the batch.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BatchConfig {
+    #[serde(default = "default_max_records")]
+    pub max_records: usize,
+    #[serde(default = "default_max_wait_ms")]
+    pub max_wait_ms: u64,
+}
+
+fn default_max_records() -> usize {
+    10_000
+}
+
+fn default_max_wait_ms() -> u64 {
+    1_000
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_records: default_max_records(),
+            max_wait_ms: default_max_wait_ms(),
+        }
+    }
+}
+/// Compression of local input files. `Auto` looks at the extension and then the magic bytes.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputCompression {
+    #[default]
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
 fn default_delimiter() -> String {
     ",".to_string()
 }
@@ -42,20 +121,68 @@ fn default_delimiter() -> String {
 fn default_true() -> bool {
     true
 }
+
+fn default_quote_char() -> String {
+    "\"".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum CsvEncodingConfig {
+    #[default]
+    Utf8,
+    // invalid utf8 is replaced with U+FFFD instead of failing the read
+    Utf8Lossy,
+}
+impl From<&CsvEncodingConfig> for polars::prelude::CsvEncoding {
+    fn from(encoding: &CsvEncodingConfig) -> Self {
+        match encoding {
+            CsvEncodingConfig::Utf8 => polars::prelude::CsvEncoding::Utf8,
+            CsvEncodingConfig::Utf8Lossy => polars::prelude::CsvEncoding::LossyUtf8,
+        }
+    }
+}
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum InputFormat {
     Csv {
         #[serde(default)]
         is_cloud: bool,
+        // a single byte, "tab", or a multi-byte string such as "||"
         #[serde(default = "default_delimiter")]
         delimiter: String,
         #[serde(default = "default_true")]
         has_header: bool,
+        // dtypes for the listed columns, the rest are inferred
+        #[serde(default)]
+        schema: Option<Schema>,
+        // empty disables quoting
+        #[serde(default = "default_quote_char")]
+        quote_char: String,
+        #[serde(default)]
+        comment_prefix: Option<String>,
+        #[serde(default)]
+        skip_rows: usize,
+        #[serde(default)]
+        null_values: Vec<String>,
+        // rows used to infer dtypes, 0 reads the whole file
+        #[serde(default)]
+        infer_schema_length: Option<usize>,
+        #[serde(default)]
+        try_parse_dates: bool,
+        #[serde(default)]
+        truncate_ragged_lines: bool,
+        #[serde(default)]
+        encoding: CsvEncodingConfig,
+    },
+    // a json document holding an array of records
+    Json {
+        // dotted path to the records array inside an envelope, e.g. "data.items"
+        #[serde(default)]
+        records_path: Option<String>,
+        // dtypes for the listed columns, the rest are inferred
         #[serde(default)]
         schema: Option<Schema>,
     },
-    Json,
     Parquet,
     Ipc,
     Avro,
@@ -65,4 +192,27 @@ pub enum InputFormat {
         #[serde(default)]
         skip_sample: bool,
     },
+    // access and service logs parsed line by line into the log_generator columns
+    NginxCombined,
+    ApacheCommon,
+    Logfmt,
+    // a table or query result from the sqlite database at `location`
+    Sqlite {
+        #[serde(default)]
+        table: Option<String>,
+        #[serde(default)]
+        query: Option<String>,
+    },
+    // RFC 5424 syslog messages, one per line or datagram
+    Syslog,
+    // named capture groups become columns, e.g. "^(?P<level>\\w+) (?P<message>.*)$"
+    Regex {
+        pattern: String,
+        // dtypes for the listed groups, the rest stay strings
+        #[serde(default)]
+        dtypes: HashMap<String, SchemaDtype>,
+        // file that receives every line the pattern does not match
+        #[serde(default)]
+        reject_path: Option<String>,
+    },
 }
@@ -1,3 +1,4 @@
+pub mod cloud;
 pub mod input;
 pub mod output;
 pub mod parse;
@@ -36,24 +36,7 @@ impl Schema {
             // If column exists, check type and constraints
             if let Ok(series) = df.column(&col.name) {
                 // Type check
-                let dtype_matches = match (&col.dtype, series.dtype()) {
-                    (SchemaDtype::Int8, DataType::Int8)
-                    | (SchemaDtype::Int16, DataType::Int16)
-                    | (SchemaDtype::Int32, DataType::Int32)
-                    | (SchemaDtype::Int64, DataType::Int64)
-                    | (SchemaDtype::UInt8, DataType::UInt8)
-                    | (SchemaDtype::UInt16, DataType::UInt16)
-                    | (SchemaDtype::UInt32, DataType::UInt32)
-                    | (SchemaDtype::UInt64, DataType::UInt64)
-                    | (SchemaDtype::Float32, DataType::Float32)
-                    | (SchemaDtype::Float64, DataType::Float64)
-                    | (SchemaDtype::Boolean, DataType::Boolean)
-                    | (SchemaDtype::Utf8, DataType::String)
-                    | (SchemaDtype::Date, DataType::Date)
-                    | (SchemaDtype::DateTime, DataType::Datetime(_, _)) => true,
-                    _ => false,
-                };
-                if !dtype_matches {
+                if !col.dtype.matches(series.dtype()) {
                     return Err(format!(
                         "Column '{}' type mismatch: expected {:?}, got {:?}",
                         col.name,
@@ -110,3 +93,62 @@ pub enum SchemaDtype {
     Date,
     DateTime,
 }
+impl Schema {
+    /// The declared columns as a polars schema, used to override inferred dtypes.
+    #[must_use]
+    pub fn to_polars_schema(&self) -> polars::prelude::Schema {
+        self.columns
+            .iter()
+            .map(|col| {
+                let dtype: polars::prelude::DataType = (&col.dtype).into();
+                (polars::prelude::PlSmallStr::from(col.name.as_str()), dtype)
+            })
+            .collect()
+    }
+}
+
+impl From<&SchemaDtype> for polars::prelude::DataType {
+    fn from(dtype: &SchemaDtype) -> Self {
+        use polars::prelude::{DataType, TimeUnit};
+        match dtype {
+            SchemaDtype::Int8 => DataType::Int8,
+            SchemaDtype::Int16 => DataType::Int16,
+            SchemaDtype::Int32 => DataType::Int32,
+            SchemaDtype::Int64 => DataType::Int64,
+            SchemaDtype::UInt8 => DataType::UInt8,
+            SchemaDtype::UInt16 => DataType::UInt16,
+            SchemaDtype::UInt32 => DataType::UInt32,
+            SchemaDtype::UInt64 => DataType::UInt64,
+            SchemaDtype::Float32 => DataType::Float32,
+            SchemaDtype::Float64 => DataType::Float64,
+            SchemaDtype::Boolean => DataType::Boolean,
+            SchemaDtype::Utf8 => DataType::String,
+            SchemaDtype::Date => DataType::Date,
+            SchemaDtype::DateTime => DataType::Datetime(TimeUnit::Microseconds, None),
+        }
+    }
+}
+
+impl SchemaDtype {
+    #[must_use]
+    pub fn matches(&self, dtype: &polars::prelude::DataType) -> bool {
+        use polars::prelude::DataType;
+        matches!(
+            (self, dtype),
+            (SchemaDtype::Int8, DataType::Int8)
+                | (SchemaDtype::Int16, DataType::Int16)
+                | (SchemaDtype::Int32, DataType::Int32)
+                | (SchemaDtype::Int64, DataType::Int64)
+                | (SchemaDtype::UInt8, DataType::UInt8)
+                | (SchemaDtype::UInt16, DataType::UInt16)
+                | (SchemaDtype::UInt32, DataType::UInt32)
+                | (SchemaDtype::UInt64, DataType::UInt64)
+                | (SchemaDtype::Float32, DataType::Float32)
+                | (SchemaDtype::Float64, DataType::Float64)
+                | (SchemaDtype::Boolean, DataType::Boolean)
+                | (SchemaDtype::Utf8, DataType::String)
+                | (SchemaDtype::Date, DataType::Date)
+                | (SchemaDtype::DateTime, DataType::Datetime(_, _))
+        )
+    }
+}
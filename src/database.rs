@@ -0,0 +1,233 @@
+use crate::{
+    configs::output::{DatabaseOutputConfig, DatabaseWriteMode},
+    outputs::OutputError,
+    sqlite,
+};
+use polars::prelude::*;
+use postgres::{Client, NoTls};
+use std::path::Path;
+
+/// Quote a possibly schema qualified name, e.g. `analytics.daily errors`.
+#[must_use]
+pub fn quote_identifier(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[must_use]
+pub fn column_list(df: &DataFrame) -> String {
+    df.get_column_names()
+        .iter()
+        .map(|name| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[must_use]
+pub fn create_table_sql(table: &str, columns: &[(String, &str)]) -> String {
+    let columns = columns
+        .iter()
+        .map(|(name, sql_type)| format!("{} {sql_type}", quote_identifier(name)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({columns})",
+        quote_identifier(table)
+    )
+}
+
+/// Upserts need a unique index on the key columns to detect conflicts. Creating it also
+/// covers tables that existed before the first upsert.
+#[must_use]
+pub fn upsert_index_sql(table: &str, key_columns: &[String]) -> String {
+    let unqualified = table.rsplit('.').next().unwrap_or(table);
+    let keys = key_columns
+        .iter()
+        .map(|key| quote_identifier(key))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({keys})",
+        quote_identifier(&format!("{unqualified}_upsert_key")),
+        quote_identifier(table)
+    )
+}
+
+/// The `ON CONFLICT` clause for the configured mode, empty unless upserting.
+#[must_use]
+pub fn conflict_clause(df: &DataFrame, config: &DatabaseOutputConfig) -> String {
+    if config.mode != DatabaseWriteMode::Upsert {
+        return String::new();
+    }
+    let keys = config
+        .key_columns
+        .iter()
+        .map(|key| quote_identifier(key))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let updates = df
+        .get_column_names()
+        .into_iter()
+        .filter(|name| !config.key_columns.iter().any(|key| key == name.as_str()))
+        .map(|name| {
+            let name = quote_identifier(name);
+            format!("{name} = excluded.{name}")
+        })
+        .collect::<Vec<_>>();
+    if updates.is_empty() {
+        format!(" ON CONFLICT ({keys}) DO NOTHING")
+    } else {
+        format!(" ON CONFLICT ({keys}) DO UPDATE SET {}", updates.join(", "))
+    }
+}
+
+/// Rows with equal key columns reduced to the last one, which is the row a row by row upsert
+/// would leave. postgres refuses to update the same row twice in one statement.
+///
+/// # Errors
+/// When a key column is not in the frame.
+pub fn last_row_per_key(df: &DataFrame, key_columns: &[String]) -> PolarsResult<DataFrame> {
+    df.unique_stable(Some(key_columns), UniqueKeepStrategy::Last, None)
+}
+
+fn validate(df: &DataFrame, config: &DatabaseOutputConfig) -> Result<(), OutputError> {
+    if config.batch_size == 0 {
+        return Err(OutputError::Config("batch_size must be at least 1".into()));
+    }
+    if config.mode == DatabaseWriteMode::Upsert && config.key_columns.is_empty() {
+        return Err(OutputError::Config(
+            "mode = \"Upsert\" needs key_columns".into(),
+        ));
+    }
+    if let Some(missing) = config
+        .key_columns
+        .iter()
+        .find(|key| df.schema().get(key.as_str()).is_none())
+    {
+        return Err(OutputError::Config(format!(
+            "key column {missing} is not in the output"
+        )));
+    }
+    Ok(())
+}
+
+/// Write a frame to the table of a `sqlite://` or `postgres://` database.
+///
+/// # Errors
+/// When the configuration is invalid or the database cannot be written.
+pub fn write_database(df: &DataFrame, config: &DatabaseOutputConfig) -> Result<(), OutputError> {
+    validate(df, config)?;
+    if let Some(path) = config.uri.strip_prefix("sqlite://") {
+        sqlite::write_sqlite(Path::new(path), df, config)
+    } else if config.uri.starts_with("postgres://") || config.uri.starts_with("postgresql://") {
+        write_postgres(df, config)
+    } else {
+        Err(OutputError::Config(format!(
+            "Unsupported database uri {}, expected sqlite:// or postgres://",
+            config.uri
+        )))
+    }
+}
+
+fn postgres_type(name: &str, dtype: &DataType) -> Result<&'static str, OutputError> {
+    Ok(match dtype {
+        DataType::Boolean => "BOOLEAN",
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => "SMALLINT",
+        DataType::Int32 | DataType::UInt16 => "INTEGER",
+        DataType::Int64 | DataType::UInt32 => "BIGINT",
+        DataType::UInt64 => "NUMERIC(20)",
+        DataType::Float32 => "REAL",
+        DataType::Float64 => "DOUBLE PRECISION",
+        DataType::String => "TEXT",
+        DataType::Date => "DATE",
+        DataType::Time => "TIME",
+        DataType::Datetime(_, None) => "TIMESTAMP",
+        DataType::Datetime(_, Some(_)) => "TIMESTAMPTZ",
+        dtype => {
+            return Err(OutputError::Config(format!(
+                "column {name} has dtype {dtype}, which cannot be written to postgres"
+            )))
+        }
+    })
+}
+
+fn write_postgres(df: &DataFrame, config: &DatabaseOutputConfig) -> Result<(), OutputError> {
+    let postgres_error = |e: postgres::Error| {
+        OutputError::Io(format!("Error writing postgres {} - {e}", config.table))
+    };
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|column| {
+            let sql_type = postgres_type(column.name(), column.dtype())?;
+            Ok((column.name().to_string(), sql_type))
+        })
+        .collect::<Result<Vec<_>, OutputError>>()?;
+    let table = quote_identifier(&config.table);
+    let column_list = column_list(df);
+    let deduplicated;
+    let df = if config.mode == DatabaseWriteMode::Upsert {
+        deduplicated = last_row_per_key(df, &config.key_columns)
+            .map_err(|e| OutputError::Other(format!("Failed to deduplicate upsert keys: {e}")))?;
+        &deduplicated
+    } else {
+        df
+    };
+
+    let mut client = Client::connect(&config.uri, NoTls).map_err(postgres_error)?;
+    // everything, including a replace, is applied in one transaction
+    let mut transaction = client.transaction().map_err(postgres_error)?;
+    if config.mode == DatabaseWriteMode::Replace {
+        transaction
+            .batch_execute(&format!("DROP TABLE IF EXISTS {table}"))
+            .map_err(postgres_error)?;
+    }
+    transaction
+        .batch_execute(&create_table_sql(&config.table, &columns))
+        .map_err(postgres_error)?;
+    // COPY cannot resolve conflicts, upserted rows are staged in a temporary table first
+    let target = if config.mode == DatabaseWriteMode::Upsert {
+        transaction
+            .batch_execute(&upsert_index_sql(&config.table, &config.key_columns))
+            .map_err(postgres_error)?;
+        transaction
+            .batch_execute(&format!(
+                "CREATE TEMPORARY TABLE polars_cli_staging (LIKE {table} INCLUDING DEFAULTS) ON COMMIT DROP"
+            ))
+            .map_err(postgres_error)?;
+        "polars_cli_staging".to_string()
+    } else {
+        table.clone()
+    };
+
+    for offset in (0..df.height()).step_by(config.batch_size) {
+        let mut batch = df.slice(
+            i64::try_from(offset).expect("row offsets fit in i64"),
+            config.batch_size,
+        );
+        let mut writer = transaction
+            .copy_in(&format!(
+                "COPY {target} ({column_list}) FROM STDIN WITH (FORMAT csv)"
+            ))
+            .map_err(postgres_error)?;
+        // quoted strings keep empty strings apart from nulls, which COPY reads as unquoted empties
+        CsvWriter::new(&mut writer)
+            .include_header(false)
+            .with_quote_style(QuoteStyle::NonNumeric)
+            .finish(&mut batch)
+            .map_err(|e| OutputError::Io(format!("Failed to write COPY rows: {e}")))?;
+        writer.finish().map_err(postgres_error)?;
+    }
+
+    if config.mode == DatabaseWriteMode::Upsert {
+        transaction
+            .batch_execute(&format!(
+                "INSERT INTO {table} ({column_list}) SELECT {column_list} FROM {target}{}",
+                conflict_clause(df, config)
+            ))
+            .map_err(postgres_error)?;
+    }
+    transaction.commit().map_err(postgres_error)
+}
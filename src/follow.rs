@@ -0,0 +1,334 @@
+use crate::{
+    config::Config,
+    configs::{
+        input::{InputConfig, InputFormat},
+        output::{DatabaseWriteMode, FileWriteMode, OutputConfig},
+    },
+    inputs::{self, InputFile},
+    runner::{self, RunnerError},
+};
+use polars::prelude::{LazyFrame, SchemaRef};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+pub struct FollowOptions {
+    /// How often the file is checked for new lines.
+    pub poll_interval: Duration,
+    /// Collect lines for this long before running the pipeline. None runs it after every
+    /// poll that found new lines.
+    pub emit_every: Option<Duration>,
+    /// Process what is already in the file before waiting for new lines.
+    pub from_beginning: bool,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            emit_every: None,
+            from_beginning: false,
+        }
+    }
+}
+
+/// Most bytes read from the followed file per poll, the rest is read on the next polls so a
+/// large backlog is worked through in bounded batches.
+pub const MAX_BATCH_BYTES: u64 = 16 * 1024 * 1024;
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+// without inodes only truncation is noticed
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> u64 {
+    0
+}
+
+/// Lines appended to a file, like `tail -F`. The file is read again from the start when it
+/// is replaced (rotation) or truncated in place (copytruncate).
+pub struct TailFile {
+    path: PathBuf,
+    file: File,
+    file_id: u64,
+    offset: u64,
+    has_header: bool,
+    header_pending: bool,
+    header: Option<Vec<u8>>,
+    partial: Vec<u8>,
+    behind: bool,
+}
+
+impl TailFile {
+    /// Start following `path`, from its end unless `from_beginning`. With `has_header` the
+    /// first line is kept apart as the header.
+    ///
+    /// # Errors
+    /// When the file cannot be opened or read.
+    pub fn open(path: &Path, from_beginning: bool, has_header: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut tail = Self {
+            path: path.to_path_buf(),
+            file,
+            file_id: file_id(&metadata),
+            offset: 0,
+            has_header,
+            header_pending: has_header,
+            header: None,
+            partial: Vec::new(),
+            behind: false,
+        };
+        if !from_beginning {
+            if has_header {
+                let mut first_line = Vec::new();
+                io::BufRead::read_until(
+                    &mut io::BufReader::new(File::open(path)?),
+                    b'\n',
+                    &mut first_line,
+                )?;
+                if first_line.ends_with(b"\n") {
+                    tail.header = Some(first_line);
+                    tail.header_pending = false;
+                }
+            }
+            tail.offset = metadata.len();
+        }
+        Ok(tail)
+    }
+
+    /// The header line of the current file, including its newline.
+    #[must_use]
+    pub fn header(&self) -> Option<&[u8]> {
+        self.header.as_deref()
+    }
+
+    /// Whether the last read stopped at `MAX_BATCH_BYTES` with more of the file left to read.
+    #[must_use]
+    pub fn is_behind(&self) -> bool {
+        self.behind
+    }
+
+    /// Complete lines written since the last call, up to about `MAX_BATCH_BYTES`. A trailing
+    /// line without a newline is held back until it is finished.
+    ///
+    /// # Errors
+    /// When the file cannot be read.
+    pub fn read_lines(&mut self) -> io::Result<Vec<u8>> {
+        let mut lines = self.read_available()?;
+        // a rotated file is only left once all of it was read
+        if self.behind {
+            return Ok(lines);
+        }
+        match fs::metadata(&self.path) {
+            Ok(metadata) if file_id(&metadata) != self.file_id => {
+                // the old file is drained, its unfinished last line will never be completed
+                if !self.partial.is_empty() {
+                    lines.append(&mut self.partial);
+                    lines.push(b'\n');
+                }
+                info!("{} was rotated, reading the new file", self.path.display());
+                self.file = File::open(&self.path)?;
+                self.file_id = file_id(&self.file.metadata()?);
+                self.restart();
+                lines.extend(self.read_available()?);
+            }
+            Ok(metadata) if metadata.len() < self.offset => {
+                info!(
+                    "{} was truncated, reading from the start",
+                    self.path.display()
+                );
+                self.partial.clear();
+                self.restart();
+                lines.extend(self.read_available()?);
+            }
+            Ok(_) => {}
+            // rotated, but the new file has not been created yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(lines)
+    }
+
+    fn restart(&mut self) {
+        self.offset = 0;
+        self.header_pending = self.has_header;
+    }
+
+    fn read_available(&mut self) -> io::Result<Vec<u8>> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = std::mem::take(&mut self.partial);
+        let read = (&mut self.file)
+            .take(MAX_BATCH_BYTES)
+            .read_to_end(&mut buffer)? as u64;
+        self.offset += read;
+        self.behind = read == MAX_BATCH_BYTES;
+        let complete = buffer
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        self.partial = buffer.split_off(complete);
+        if self.header_pending && !buffer.is_empty() {
+            let header_end = buffer
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .expect("complete lines end in a newline");
+            let rest = buffer.split_off(header_end + 1);
+            self.header = Some(buffer);
+            self.header_pending = false;
+            return Ok(rest);
+        }
+        Ok(buffer)
+    }
+}
+
+/// Runs batches of lines through the configured operations. A JSON lines schema is inferred
+/// from the first batch and reused for the rest, so batches are not sampled one by one and
+/// fields that only show up later are left out.
+pub struct Batches<'a> {
+    config: &'a Config,
+    input: &'a InputConfig,
+    jsonl_schema: Option<SchemaRef>,
+}
+
+impl<'a> Batches<'a> {
+    /// # Errors
+    /// When the configuration has no input.
+    pub fn new(config: &'a Config) -> Result<Self, RunnerError> {
+        let input = config
+            .input
+            .as_ref()
+            .ok_or_else(|| RunnerError::Other("No input provided in the configuration.".into()))?;
+        Ok(Self {
+            config,
+            input,
+            jsonl_schema: None,
+        })
+    }
+
+    /// Run the configured operations over one batch of lines read from the input.
+    ///
+    /// # Errors
+    /// When the lines cannot be read in the input's format or an operation fails.
+    pub fn dataframe(
+        &mut self,
+        header: Option<&[u8]>,
+        lines: &[u8],
+    ) -> Result<LazyFrame, RunnerError> {
+        let copy = inputs::TempFile::new("follow_batch");
+        let mut file = File::create(copy.path())?;
+        if let Some(header) = header {
+            file.write_all(header)?;
+        }
+        file.write_all(lines)?;
+        if self.jsonl_schema.is_none()
+            && matches!(
+                self.input.format,
+                InputFormat::JsonLines {
+                    skip_sample: false,
+                    ..
+                }
+            )
+        {
+            self.jsonl_schema = Some(inputs::infer_jsonl_schema(&[copy.path().to_path_buf()])?);
+        }
+        let batch = InputFile::copy(PathBuf::from(&self.input.location), copy);
+        let df = runner::dataframe_from_files(self.input, vec![batch], self.jsonl_schema.clone())?;
+        runner::process_dataframe(df, self.config)
+    }
+}
+
+/// Batches are written one after the other, so outputs that replace what is there would only
+/// ever hold the last batch. File outputs must append and Database outputs must not replace.
+///
+/// # Errors
+/// When an output would overwrite the previous batches.
+pub fn check_batch_outputs(config: &Config) -> Result<(), RunnerError> {
+    for output in config.outputs.iter().flatten() {
+        let replaced = match output {
+            OutputConfig::File(file) if file.mode != FileWriteMode::Append => {
+                Some(format!("{} needs mode = \"Append\"", file.path))
+            }
+            OutputConfig::Database(database) if database.mode == DatabaseWriteMode::Replace => {
+                Some(format!("{} cannot use mode = \"Replace\"", database.table))
+            }
+            OutputConfig::Cloud(cloud) => Some(format!(
+                "{} cannot be appended to, cloud objects are replaced",
+                cloud.key
+            )),
+            _ => None,
+        };
+        if let Some(reason) = replaced {
+            return Err(RunnerError::Other(format!(
+                "every batch would overwrite the last: {reason}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Keep reading lines appended to the input file and write each micro-batch to the
+/// outputs. Only returns on error.
+///
+/// # Errors
+/// When the input cannot be followed, a batch fails or an output cannot be written.
+pub fn follow(config: &Config, options: &FollowOptions) -> Result<(), RunnerError> {
+    let input = config
+        .input
+        .as_ref()
+        .ok_or_else(|| RunnerError::Other("No input provided in the configuration.".into()))?;
+    let has_header = match &input.format {
+        InputFormat::Csv { has_header, .. } => *has_header,
+        InputFormat::JsonLines { .. }
+        | InputFormat::NginxCombined
+        | InputFormat::ApacheCommon
+        | InputFormat::Logfmt
+        | InputFormat::Syslog
+        | InputFormat::Regex { .. } => false,
+        format => {
+            return Err(RunnerError::Other(format!(
+                "--follow needs a line based input, {format:?} is not one"
+            )))
+        }
+    };
+    if input.is_stdin() || inputs::is_cloud_location(&input.location) {
+        return Err(RunnerError::Other(
+            "--follow needs a local file as input location".into(),
+        ));
+    }
+    check_batch_outputs(config)?;
+
+    let path = PathBuf::from(&input.location);
+    let mut tail = TailFile::open(&path, options.from_beginning, has_header)?;
+    let mut batches = Batches::new(config)?;
+    info!("Following {}", path.display());
+    let mut pending = Vec::new();
+    let mut last_emit = Instant::now();
+    loop {
+        pending.extend(tail.read_lines()?);
+        // a backlog is emitted in capped batches even when the interval has not passed
+        let due = pending.len() as u64 >= MAX_BATCH_BYTES
+            || options
+                .emit_every
+                .is_none_or(|every| last_emit.elapsed() >= every);
+        if due {
+            if !pending.is_empty() {
+                let lines = std::mem::take(&mut pending);
+                batches
+                    .dataframe(tail.header(), &lines)
+                    .and_then(|df| runner::write_dataframe(df, config))?;
+            }
+            last_emit = Instant::now();
+        }
+        if !tail.is_behind() {
+            thread::sleep(options.poll_interval);
+        }
+    }
+}
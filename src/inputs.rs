@@ -0,0 +1,521 @@
+use crate::configs::input::{InputCompression, STDIN_LOCATION};
+use polars::prelude::*;
+use polars_io::ipc::IpcWriter;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Number of lines read across all files to infer a JSON lines schema.
+pub const SCHEMA_SAMPLE_LINES: usize = 10_000;
+
+#[must_use]
+pub fn is_cloud_location(location: &str) -> bool {
+    location.contains("://") && !location.starts_with("file://") && !is_listen_location(location)
+}
+
+/// A `tcp://host:port` or `udp://host:port` location, records are received on that socket.
+#[must_use]
+pub fn is_listen_location(location: &str) -> bool {
+    location.starts_with("tcp://") || location.starts_with("udp://")
+}
+
+/// Expand a location into the files it refers to. A location can be a single file, a
+/// directory (every visible file below it) or a glob such as `logs/2024-04-*/*.jsonl`.
+/// Cloud urls are returned untouched, polars expands globs for those itself.
+///
+/// # Errors
+/// When the directory cannot be listed, the glob is invalid or nothing matches.
+pub fn resolve_paths(location: &str) -> Result<Vec<PathBuf>, String> {
+    if is_cloud_location(location) {
+        return Ok(vec![PathBuf::from(location)]);
+    }
+    let location = location.strip_prefix("file://").unwrap_or(location);
+    let path = Path::new(location);
+    let mut paths = if path.is_dir() {
+        let mut paths = Vec::new();
+        collect_dir(path, &mut paths)
+            .map_err(|e| format!("Error listing input directory '{location}' - {e}"))?;
+        paths
+    } else if location.contains(['*', '?', '[']) {
+        glob::glob(location)
+            .map_err(|e| format!("Invalid input glob '{location}' - {e}"))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    if paths.is_empty() {
+        return Err(format!("No input files found for '{location}'"));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// hidden and underscore files are markers such as _SUCCESS or .crc, not data
+fn collect_dir(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.') || name.starts_with('_'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_dir(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Infer a JSON lines schema from the first lines of every file so a column that only shows
+/// up in later files still ends up in the schema.
+///
+/// # Errors
+/// When a file cannot be read or the sample is not valid JSON lines.
+pub fn infer_jsonl_schema(paths: &[PathBuf]) -> Result<SchemaRef, String> {
+    let per_file = (SCHEMA_SAMPLE_LINES / paths.len().max(1)).max(1);
+    let mut sample = Vec::new();
+    for path in paths {
+        let file = File::open(path).map_err(|e| {
+            format!(
+                "Error reading local file for schema extraction: {} - {e}",
+                path.display()
+            )
+        })?;
+        for line in BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .take(per_file)
+        {
+            sample.extend_from_slice(line.as_bytes());
+            sample.push(b'\n');
+        }
+    }
+    infer_jsonl_schema_from_bytes(sample)
+}
+
+/// Infer a JSON lines schema from sample lines.
+///
+/// # Errors
+/// When the sample is not valid JSON lines.
+pub fn infer_jsonl_schema_from_bytes(sample: Vec<u8>) -> Result<SchemaRef, String> {
+    let df_sample = JsonLineReader::new(Cursor::new(sample))
+        .infer_schema_len(std::num::NonZeroUsize::new(SCHEMA_SAMPLE_LINES))
+        .finish()
+        .map_err(|e| format!("Error inferring schema from sample - {e}"))?;
+    Ok(df_sample.schema().clone())
+}
+
+/// A file in the temp dir that is removed when dropped, such as a decompressed copy of an
+/// input. Files opened before the drop stay readable on Unix, which is how lazy scans of a
+/// copy outlive it.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let pid = std::process::id();
+        let rand = rand::random::<u64>();
+        let path = std::env::temp_dir().join(format!("polars_cli_{pid}_{rand}_{name}"));
+        Self { path }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => tracing::warn!(
+                "Could not remove temporary file {} - {e}",
+                self.path.display()
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// A resolved input file. `path` is what gets read, which differs from `original` when the
+/// file had to be decompressed or rewritten into a temporary copy first.
+#[derive(Debug)]
+pub struct InputFile {
+    pub original: PathBuf,
+    pub path: PathBuf,
+    // removes the copy together with this file
+    copy: Option<TempFile>,
+}
+
+impl InputFile {
+    #[must_use]
+    pub fn local(path: PathBuf) -> Self {
+        Self {
+            path: path.clone(),
+            original: path,
+            copy: None,
+        }
+    }
+
+    #[must_use]
+    pub fn copy(original: PathBuf, copy: TempFile) -> Self {
+        Self {
+            original,
+            path: copy.path().to_path_buf(),
+            copy: Some(copy),
+        }
+    }
+
+    #[must_use]
+    pub fn is_copy(&self) -> bool {
+        self.copy.is_some()
+    }
+
+    /// What a lazy scan should read. A temporary copy is handed over as an open file, so the
+    /// copy can be removed from disk once the plan is built, however the plan ends later.
+    ///
+    /// # Errors
+    /// When the temporary copy cannot be opened.
+    pub fn scan_source(&self) -> io::Result<ScanSources> {
+        if self.is_copy() {
+            Ok(ScanSources::Files(Arc::from([File::open(&self.path)?])))
+        } else {
+            Ok(ScanSources::Paths(Arc::from([self.path.clone()])))
+        }
+    }
+}
+
+/// Detect the compression of a file from its extension, falling back to its magic bytes.
+///
+/// # Errors
+/// When the file cannot be opened or read.
+pub fn detect_compression(path: &Path) -> io::Result<InputCompression> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("gz" | "gzip") => return Ok(InputCompression::Gzip),
+        Some("zst" | "zstd") => return Ok(InputCompression::Zstd),
+        Some("bz2") => return Ok(InputCompression::Bzip2),
+        _ => {}
+    }
+    let mut magic = [0u8; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(compression_from_magic(&magic[..read]))
+}
+
+#[must_use]
+pub fn compression_from_magic(magic: &[u8]) -> InputCompression {
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        InputCompression::Gzip
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        InputCompression::Zstd
+    } else if magic.starts_with(b"BZh") {
+        InputCompression::Bzip2
+    } else {
+        InputCompression::None
+    }
+}
+
+/// Wrap a reader in a streaming decoder for the given compression.
+///
+/// # Errors
+/// When the zstd decoder cannot be set up.
+pub fn decompressing_reader<'a, R: BufRead + 'a>(
+    reader: R,
+    compression: InputCompression,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        InputCompression::Auto | InputCompression::None => Box::new(reader),
+        // rotated logs are often several gzip members appended together
+        InputCompression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        InputCompression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        InputCompression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+    })
+}
+
+/// Standard input behind a streaming decoder. Compressed streams are detected from their magic
+/// bytes unless `compression` is set.
+fn stdin_reader(compression: InputCompression) -> Result<Box<dyn Read>, String> {
+    let mut reader = BufReader::new(io::stdin().lock());
+    let compression = match compression {
+        InputCompression::Auto => {
+            let magic = reader
+                .fill_buf()
+                .map_err(|e| format!("Error reading stdin - {e}"))?;
+            compression_from_magic(magic)
+        }
+        compression => compression,
+    };
+    decompressing_reader(reader, compression).map_err(|e| format!("Error reading stdin - {e}"))
+}
+
+/// Copy standard input into a temporary file so it can be scanned like any local file.
+///
+/// # Errors
+/// When standard input cannot be read or the copy cannot be written.
+pub fn spool_stdin(compression: InputCompression) -> Result<InputFile, String> {
+    let mut decoder = stdin_reader(compression)?;
+    let copy = TempFile::new("stdin");
+    let mut spool = || -> io::Result<u64> {
+        let mut writer = BufWriter::new(File::create(copy.path())?);
+        io::copy(&mut decoder, &mut writer)
+    };
+    let bytes = spool().map_err(|e| format!("Error buffering stdin - {e}"))?;
+    tracing::info!("Buffered {bytes} bytes from stdin");
+    Ok(InputFile::copy(PathBuf::from(STDIN_LOCATION), copy))
+}
+
+/// Lines of a JSON lines stream parsed at a time once its schema is known.
+pub const STDIN_BATCH_LINES: usize = 50_000;
+
+/// Append up to `lines` non empty lines to `buffer`, fewer only at the end of the stream.
+fn read_lines(reader: &mut impl BufRead, lines: usize, buffer: &mut Vec<u8>) -> io::Result<()> {
+    let mut read = 0;
+    while read < lines {
+        let start = buffer.len();
+        if reader.read_until(b'\n', buffer)? == 0 {
+            break;
+        }
+        if buffer[start..].iter().all(u8::is_ascii_whitespace) {
+            buffer.truncate(start);
+        } else {
+            read += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Read JSON lines from standard input as they arrive. The schema is inferred from a buffered
+/// prefix of `SCHEMA_SAMPLE_LINES` lines, then the prefix and the rest of the stream are parsed
+/// `STDIN_BATCH_LINES` at a time into an Arrow IPC copy for the lazy scan, so memory holds one
+/// batch rather than the whole stream.
+///
+/// # Errors
+/// When standard input cannot be read or holds no valid JSON lines.
+pub fn stdin_json_lines(compression: InputCompression) -> Result<InputFile, String> {
+    let mut reader = BufReader::new(stdin_reader(compression)?);
+    let mut batch = Vec::new();
+    read_lines(&mut reader, SCHEMA_SAMPLE_LINES, &mut batch)
+        .map_err(|e| format!("Error reading stdin - {e}"))?;
+    let schema = infer_jsonl_schema_from_bytes(batch.clone())?;
+
+    let copy = TempFile::new("stdin.arrow");
+    let mut parse = || -> PolarsResult<usize> {
+        let mut writer = IpcWriter::new(File::create(copy.path())?).batched(&schema)?;
+        let mut rows = 0;
+        while !batch.is_empty() {
+            let df = JsonLineReader::new(Cursor::new(std::mem::take(&mut batch)))
+                .with_schema(schema.clone())
+                .with_ignore_errors(true)
+                .finish()?;
+            rows += df.height();
+            writer.write_batch(&df)?;
+            read_lines(&mut reader, STDIN_BATCH_LINES, &mut batch)?;
+        }
+        writer.finish()?;
+        Ok(rows)
+    };
+    let rows = parse().map_err(|e| format!("Error reading JSON lines from stdin - {e}"))?;
+    tracing::info!("Read {rows} rows from stdin");
+    Ok(InputFile::copy(PathBuf::from(STDIN_LOCATION), copy))
+}
+
+/// Decompress local compressed files into temporary copies so every reader, including the
+/// lazy scans, sees plain data. polars can only scan seekable files, so the data is streamed
+/// through the decoder into the copy, which needs as much disk space as the plain data but
+/// never holds more than a buffer in memory.
+///
+/// # Errors
+/// When a file cannot be read or decompressed. Copies made so far are removed again.
+pub fn decompress_inputs(
+    paths: Vec<PathBuf>,
+    compression: InputCompression,
+) -> Result<Vec<InputFile>, String> {
+    let mut files = Vec::with_capacity(paths.len());
+    for original in paths {
+        let location = original.to_string_lossy();
+        let file_compression = match compression {
+            _ if is_cloud_location(&location) => InputCompression::None,
+            InputCompression::Auto => detect_compression(&original).map_err(|e| {
+                format!(
+                    "Error detecting compression of {} - {e}",
+                    original.display()
+                )
+            })?,
+            compression => compression,
+        };
+        if file_compression == InputCompression::None {
+            files.push(InputFile::local(original));
+            continue;
+        }
+        let name = original
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let copy = TempFile::new(&name);
+        let decompress = || -> io::Result<u64> {
+            let reader = BufReader::new(File::open(&original)?);
+            let mut decoder = decompressing_reader(reader, file_compression)?;
+            let mut writer = BufWriter::new(File::create(copy.path())?);
+            io::copy(&mut decoder, &mut writer)
+        };
+        let bytes = decompress()
+            .map_err(|e| format!("Error decompressing {} - {e}", original.display()))?;
+        tracing::info!(
+            "Decompressed {} ({file_compression:?}, {bytes} bytes)",
+            original.display()
+        );
+        files.push(InputFile::copy(original, copy));
+    }
+    Ok(files)
+}
+
+/// Read a json document and return the bytes of its records array. Without `records_path`
+/// the document itself must be the array. A single object is treated as one record.
+///
+/// # Errors
+/// When the file cannot be read, is not valid json or `records_path` leads to no array.
+pub fn json_records(path: &Path, records_path: Option<&str>) -> Result<Vec<u8>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Error reading json input {} - {e}", path.display()))?;
+    let Some(records_path) = records_path else {
+        return Ok(bytes);
+    };
+    let document: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Error parsing json input {} - {e}", path.display()))?;
+    let mut records = &document;
+    for key in records_path.split('.').filter(|key| !key.is_empty()) {
+        records = match records {
+            serde_json::Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            value => value.get(key),
+        }
+        .ok_or_else(|| {
+            format!(
+                "records_path '{records_path}' not found in {}, missing '{key}'",
+                path.display()
+            )
+        })?;
+    }
+    serde_json::to_vec(records).map_err(|e| format!("Error extracting json records - {e}"))
+}
+
+/// Byte substituted for multi-byte CSV delimiters, the ASCII unit separator.
+pub const REWRITTEN_DELIMITER: u8 = 0x1f;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvSeparator {
+    Byte(u8),
+    Multi(Vec<u8>),
+}
+
+/// Parse a configured CSV delimiter, `tab` stands for a tab.
+///
+/// # Errors
+/// When the delimiter is empty.
+pub fn csv_separator(delimiter: &str) -> Result<CsvSeparator, String> {
+    match delimiter {
+        "" => Err("CSV delimiter is empty".to_string()),
+        "tab" | "\\t" => Ok(CsvSeparator::Byte(b'\t')),
+        delimiter if delimiter.len() == 1 => Ok(CsvSeparator::Byte(delimiter.as_bytes()[0])),
+        delimiter => Ok(CsvSeparator::Multi(delimiter.as_bytes().to_vec())),
+    }
+}
+
+/// polars only splits on a single byte, so multi-byte delimiters are rewritten to
+/// `REWRITTEN_DELIMITER` in a temporary copy. Delimiters inside quoted fields and comment lines
+/// are kept. The copy costs a full read and write of every file before polars scans it, and as
+/// much disk space as the input.
+///
+/// # Errors
+/// When a file cannot be read or its copy written.
+pub fn rewrite_delimiter(
+    files: Vec<InputFile>,
+    delimiter: &[u8],
+    quote_char: Option<u8>,
+    comment_prefix: Option<&[u8]>,
+) -> Result<Vec<InputFile>, String> {
+    let mut rewritten = Vec::with_capacity(files.len());
+    for file in files {
+        let name = file
+            .original
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let copy = TempFile::new(&name);
+        let rewrite = || -> io::Result<()> {
+            let reader = BufReader::new(File::open(&file.path)?);
+            let mut writer = BufWriter::new(File::create(copy.path())?);
+            replace_delimiter(reader, &mut writer, delimiter, quote_char, comment_prefix)
+        };
+        rewrite().map_err(|e| {
+            format!(
+                "Error rewriting delimiter of {} - {e}",
+                file.original.display()
+            )
+        })?;
+        rewritten.push(InputFile::copy(file.original.clone(), copy));
+    }
+    Ok(rewritten)
+}
+
+fn replace_delimiter<R: BufRead, W: io::Write>(
+    mut reader: R,
+    writer: &mut W,
+    delimiter: &[u8],
+    quote_char: Option<u8>,
+    comment_prefix: Option<&[u8]>,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut pending: Vec<u8> = Vec::with_capacity(delimiter.len());
+    let mut in_quotes = false;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        // a quote in a comment must not open a field for the rows after it
+        if !in_quotes && comment_prefix.is_some_and(|prefix| line.starts_with(prefix)) {
+            writer.write_all(&line)?;
+            continue;
+        }
+        for &byte in &line {
+            if Some(byte) == quote_char {
+                writer.write_all(&pending)?;
+                pending.clear();
+                in_quotes = !in_quotes;
+                writer.write_all(&[byte])?;
+                continue;
+            }
+            if in_quotes {
+                writer.write_all(&[byte])?;
+                continue;
+            }
+            pending.push(byte);
+            while !pending.is_empty() && !delimiter.starts_with(&pending) {
+                writer.write_all(&pending[..1])?;
+                pending.remove(0);
+            }
+            if pending == delimiter {
+                writer.write_all(&[REWRITTEN_DELIMITER])?;
+                pending.clear();
+            }
+        }
+    }
+    writer.write_all(&pending)?;
+    writer.flush()
+}
@@ -1,5 +1,13 @@
 #![deny(clippy::all, clippy::pedantic)]
+pub mod avro;
 pub mod config;
 pub mod configs;
+pub mod database;
+pub mod follow;
+pub mod inputs;
+pub mod listen;
 pub mod outputs;
+pub mod parsers;
+pub mod render;
 pub mod runner;
+pub mod sqlite;
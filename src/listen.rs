@@ -0,0 +1,198 @@
+use crate::{
+    config::Config,
+    configs::input::{BatchConfig, InputFormat},
+    follow, parsers,
+    runner::{self, RunnerError},
+};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, UdpSocket},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info, warn};
+
+// largest payload a udp datagram can carry
+const MAX_DATAGRAM: usize = 64 * 1024;
+
+/// Records held between the socket and the pipeline. Once it is full the socket is no longer
+/// read, so tcp senders are slowed down and the OS drops udp datagrams its buffer cannot hold.
+pub const MAX_PENDING_RECORDS: usize = 100_000;
+
+/// Records received on a local socket. The socket is served by a tokio runtime on a
+/// background thread that forwards every record, so batches are processed synchronously.
+pub struct Listener {
+    local_addr: SocketAddr,
+    records: Receiver<Vec<u8>>,
+}
+
+impl Listener {
+    /// Bind a `tcp://host:port` or `udp://host:port` location. Port 0 picks a free port.
+    ///
+    /// # Errors
+    /// When the location is not a tcp:// or udp:// address or cannot be bound.
+    pub fn bind(location: &str) -> io::Result<Self> {
+        let (sender, records) = mpsc::sync_channel(MAX_PENDING_RECORDS);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let local_addr = if let Some(address) = location.strip_prefix("tcp://") {
+            let listener = TcpListener::bind(address)?;
+            listener.set_nonblocking(true)?;
+            let local_addr = listener.local_addr()?;
+            thread::spawn(move || runtime.block_on(serve_tcp(listener, sender)));
+            local_addr
+        } else if let Some(address) = location.strip_prefix("udp://") {
+            let socket = UdpSocket::bind(address)?;
+            socket.set_nonblocking(true)?;
+            let local_addr = socket.local_addr()?;
+            thread::spawn(move || runtime.block_on(serve_udp(socket, sender)));
+            local_addr
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{location} is not a tcp:// or udp:// location"),
+            ));
+        };
+        Ok(Self {
+            local_addr,
+            records,
+        })
+    }
+
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Wait for the next batch of newline terminated records. A batch closes once it holds
+    /// `max_records` or `max_wait_ms` has passed since its first record arrived.
+    #[must_use]
+    pub fn next_batch(&self, batch: &BatchConfig) -> Option<Vec<u8>> {
+        let mut lines = self.records.recv().ok()?;
+        lines.push(b'\n');
+        let deadline = Instant::now() + Duration::from_millis(batch.max_wait_ms);
+        let mut count = 1;
+        while count < batch.max_records {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Ok(record) = self.records.recv_timeout(remaining) else {
+                break;
+            };
+            lines.extend(record);
+            lines.push(b'\n');
+            count += 1;
+        }
+        Some(lines)
+    }
+}
+
+// blank lines are skipped, \r\n framing is accepted
+fn record(line: &[u8]) -> Option<Vec<u8>> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    (!line.iter().all(u8::is_ascii_whitespace)).then(|| line.to_vec())
+}
+
+// waits while the pipeline is behind, false once the listener is gone
+fn forward(sender: &SyncSender<Vec<u8>>, record: Vec<u8>) -> bool {
+    tokio::task::block_in_place(|| sender.send(record).is_ok())
+}
+
+async fn serve_tcp(listener: TcpListener, sender: SyncSender<Vec<u8>>) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Could not serve tcp listener - {e}");
+            return;
+        }
+    };
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Could not accept connection - {e}");
+                continue;
+            }
+        };
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stream).split(b'\n');
+            loop {
+                match lines.next_segment().await {
+                    Ok(Some(line)) => {
+                        if let Some(record) = record(&line) {
+                            if !forward(&sender, record) {
+                                return;
+                            }
+                        }
+                    }
+                    Ok(None) => return,
+                    Err(e) => {
+                        warn!("Connection from {peer} failed - {e}");
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn serve_udp(socket: UdpSocket, sender: SyncSender<Vec<u8>>) {
+    let socket = match tokio::net::UdpSocket::from_std(socket) {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("Could not serve udp socket - {e}");
+            return;
+        }
+    };
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    loop {
+        let length = match socket.recv_from(&mut buffer).await {
+            Ok((length, _)) => length,
+            Err(e) => {
+                warn!("Could not receive datagram - {e}");
+                continue;
+            }
+        };
+        // usually one message per datagram, but some senders pack several lines
+        for line in buffer[..length].split(|byte| *byte == b'\n') {
+            if let Some(record) = record(line) {
+                if !forward(&sender, record) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Receive records on the input's tcp:// or udp:// location and write every micro-batch to
+/// the outputs. Only returns on error.
+///
+/// # Errors
+/// When the input is not line based, the socket cannot be bound, a batch fails or an output
+/// cannot be written.
+pub fn listen(config: &Config) -> Result<(), RunnerError> {
+    let input = config
+        .input
+        .as_ref()
+        .ok_or_else(|| RunnerError::Other("No input provided in the configuration.".into()))?;
+    let line_based = matches!(input.format, InputFormat::JsonLines { .. })
+        || parsers::line_parser(&input.format)?.is_some();
+    if !line_based {
+        return Err(RunnerError::Other(format!(
+            "{} needs a line based input format, {:?} is not one",
+            input.location, input.format
+        )));
+    }
+    follow::check_batch_outputs(config)?;
+    let listener = Listener::bind(&input.location)?;
+    info!("Listening on {}", listener.local_addr());
+    let mut batches = follow::Batches::new(config)?;
+    while let Some(lines) = listener.next_batch(&input.batch) {
+        batches
+            .dataframe(None, &lines)
+            .and_then(|df| runner::write_dataframe(df, config))?;
+    }
+    Ok(())
+}
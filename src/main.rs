@@ -1,8 +1,14 @@
 use clap::Parser;
 use clap_derive::Parser;
 use polars_cli::{
-    configs::input::InputConfig, configs::parse::parse_config_file, runner::run_with_output,
+    configs::input::{InputConfig, STDIN_LOCATION},
+    configs::parse::parse_config_file,
+    follow::{follow, FollowOptions},
+    inputs::is_listen_location,
+    listen::listen,
+    runner::run_with_output,
 };
+use std::time::Duration;
 use tracing::info;
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -26,10 +32,33 @@ struct Cli {
     /// Cloud provider url (e.g., s3, gcs, azure)
     #[clap(long)]
     cloud_provider: Option<String>,
+
+    /// Read the input from stdin, same as an input location of "-"
+    #[clap(long)]
+    stdin: bool,
+
+    /// Keep the input file open and process new lines as they are appended, like tail -F
+    #[clap(long)]
+    follow: bool,
+
+    /// With --follow, how often to check the file for new lines
+    #[clap(long, default_value_t = 500)]
+    poll_interval_ms: u64,
+
+    /// With --follow, write outputs every N seconds instead of after every new batch
+    #[clap(long)]
+    emit_every_secs: Option<u64>,
+
+    /// With --follow, process the lines already in the file first
+    #[clap(long)]
+    from_beginning: bool,
 }
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    // stdout carries Stdout outputs, e.g. into the next command of a shell pipeline
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
     let cli = Cli::parse();
     info!("Parsing TOML configuration from: {}", cli.config);
@@ -38,11 +67,19 @@ fn main() {
     if cli.parse {
         std::process::exit(0);
     } else {
+        if cli.stdin {
+            if let Some(input) = config.input.as_mut() {
+                input.location = STDIN_LOCATION.to_string();
+            }
+        }
         if config.input.is_none() {
-            let input_path = cli
-                .local_input
-                .or_else(|| cli.cloud_provider.clone())
-                .expect("Input path is required if not present in the config file.");
+            let input_path = if cli.stdin {
+                STDIN_LOCATION.to_string()
+            } else {
+                cli.local_input
+                    .or_else(|| cli.cloud_provider.clone())
+                    .expect("Input path is required if not present in the config file.")
+            };
 
             let file_format = cli
                 .file_format
@@ -57,7 +94,23 @@ fn main() {
                 false,
             ));
         }
-        if let Err(e) = run_with_output(config) {
+        let listening = config
+            .input
+            .as_ref()
+            .is_some_and(|input| is_listen_location(&input.location));
+        let result = if listening {
+            listen(&config)
+        } else if cli.follow {
+            let options = FollowOptions {
+                poll_interval: Duration::from_millis(cli.poll_interval_ms),
+                emit_every: cli.emit_every_secs.map(Duration::from_secs),
+                from_beginning: cli.from_beginning,
+            };
+            follow(&config, &options)
+        } else {
+            run_with_output(config)
+        };
+        if let Err(e) = result {
             eprintln!("Application error: {:>}", e);
             std::process::exit(1);
         }
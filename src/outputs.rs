@@ -1,22 +1,30 @@
+use flate2::write::GzEncoder;
 use polars::prelude::{
-    file::DynWriteable, sync_on_close::SyncOnCloseType, CsvWriter, CsvWriterOptions, LazyFrame,
-    ParquetWriteOptions, ParquetWriter, SinkOptions, SinkTarget, SpecialEq,
+    file::DynWriteable, sync_on_close::SyncOnCloseType, AnyValue, CsvWriter, DataFrame, IntoLazy,
+    LazyFrame, ParquetWriter, SinkOptions, SinkTarget, SpecialEq,
 };
 use polars_io::{
-    avro::AvroWriter,
+    cloud::CloudOptions,
     ipc::{IpcStreamWriter, IpcWriterOptions},
     json::{JsonFormat, JsonWriter, JsonWriterOptions},
     SerWriter,
 };
 use std::{
-    fs::File,
-    io::{self, Write},
-    sync::{Arc, Mutex},
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
 
-use crate::configs::output::{
-    CloudOutputConfig, DatabaseOutputConfig, FileOutputConfig, FormatOutputConfig, OutputConfig,
-    OutputFormats,
+use crate::{
+    avro,
+    configs::output::{
+        CloudOutputConfig, CsvWriterConfig, DatabaseOutputConfig, FileOutputConfig, FileWriteMode,
+        FormatOutputConfig, JsonCompressionConfig, JsonWriterConfig, OutputConfig, OutputFormats,
+        PartitionConfig, RollingConfig, WriterOptions,
+    },
+    database, render,
 };
 
 #[derive(Debug)]
@@ -47,87 +55,59 @@ impl From<String> for OutputError {
 }
 
 pub trait OutputConnector {
-    fn format(&self) -> OutputFormats;
+    /// The file format rows are written in, None for outputs that write rows another way.
+    fn format(&self) -> Option<OutputFormats> {
+        None
+    }
     fn sink_target(&self) -> Option<SinkTarget> {
         None
     }
+    /// Credentials and settings for sinks to a cloud url, None for local targets.
+    ///
+    /// # Errors
+    /// When the cloud settings do not fit the target url.
+    fn cloud_options(&self) -> Result<Option<CloudOptions>, OutputError> {
+        Ok(None)
+    }
+    /// The Csv, Parquet and Json settings of this output.
+    fn writer_options(&self) -> WriterOptions {
+        WriterOptions::default()
+    }
+    /// Called once everything is written, e.g. to move a temporary file into place.
+    ///
+    /// # Errors
+    /// When the written output cannot be put in place.
+    fn finish(&self) -> Result<(), OutputError> {
+        Ok(())
+    }
+    /// Called when writing failed part way, to clean up what `finish` would have published.
+    fn discard(&self) {}
+    /// The file `finish` publishes, for writers that wrap the sink themselves. None when the
+    /// output is not a local file.
+    ///
+    /// # Errors
+    /// When the file cannot be opened.
+    fn temp_file(&self) -> Result<Option<File>, OutputError> {
+        Ok(None)
+    }
 
     fn stream(&self, df: LazyFrame) -> Result<(), OutputError> {
-        let sink_options = SinkOptions {
-            sync_on_close: SyncOnCloseType::Data,
-            maintain_order: true,
-            mkdir: true,
-        };
-        let target = self.sink_target().expect("Sink target should never fail");
-        dbg!(&target);
-
-        let result = match &self.format() {
-            OutputFormats::Csv => LazyFrame::sink_csv(
-                df,
-                target,
-                CsvWriterOptions {
-                    include_header: true,
-                    ..Default::default()
-                },
-                None,
-                sink_options,
-            )
-            .map_err(|e| OutputError::Io(format!("Failed to write CSV sink: {}", e)))?,
-            OutputFormats::Json => {
-                LazyFrame::sink_json(df, target, JsonWriterOptions::default(), None, sink_options)
-                    .map_err(|e| OutputError::Io(format!("Failed to write Json sink: {}", e)))?
-            }
-            OutputFormats::Jsonl => {
-                LazyFrame::sink_json(df, target, JsonWriterOptions {}, None, sink_options)
-                    .map_err(|e| OutputError::Io(format!("Failed to write Json sink: {}", e)))?
-            }
-            OutputFormats::Parquet => LazyFrame::sink_parquet(
-                df,
-                target,
-                ParquetWriteOptions::default(),
-                None,
-                sink_options,
-            )
-            .map_err(|e| OutputError::Io(format!("Failed to write Parquet sink: {}", e)))?,
-            OutputFormats::Avro => {
-                todo!();
-                /*
-                               let mut df = df.collect().map_err(|e| {
-                                   OutputError::Io(format!("Failed to collect DataFrame for Avro: {}", e))
-                               })?;
-                               AvroWriter::new(&mut file)
-                                   .finish(&mut df)
-                                   .map_err(|e| OutputError::Io(format!("Failed to write Arrow: {}", e)))?
-                */
-            }
-            OutputFormats::Icp { compression } => LazyFrame::sink_ipc(
-                df,
-                target,
-                IpcWriterOptions {
-                    compression: compression.as_ref().map(Into::into),
-                    ..Default::default()
-                },
-                None,
-                sink_options,
-            )
-            .map_err(|e| OutputError::Io(format!("Failed to write ICP sink: {}", e)))?,
-        };
-        result
-            .collect()
-            .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame for sink: {}", e)))?;
-        Ok(())
+        sink_frame(self, df)
     }
 
     fn write(&self, df: LazyFrame) -> Result<(), OutputError> {
         let mut df = df
             .collect()
             .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {}", e)))?;
-        let mut file = self.file();
-        match &self.format() {
+        let format = file_format(self)?;
+        let options = self.writer_options();
+        let mut file = self.file()?;
+        if matches!(format, OutputFormats::Json | OutputFormats::Jsonl) {
+            file = compress_writer(file, &options.json)?;
+        }
+        match &format {
             OutputFormats::Csv => {
-                CsvWriter::new(&mut file)
-                    .include_header(true)
-                    .with_separator(b',')
+                csv_writer(&mut file, &options.csv)?
                     .finish(&mut df)
                     .map_err(|e| OutputError::Io(format!("Failed to write CSV: {}", e)))?;
             }
@@ -144,14 +124,17 @@ pub trait OutputConnector {
                     .map_err(|e| OutputError::Io(format!("Failed to write JSONLine: {}", e)))?;
             }
             OutputFormats::Parquet => {
+                let parquet = options.parquet.to_polars().map_err(OutputError::Config)?;
                 ParquetWriter::new(&mut file)
+                    .with_compression(parquet.compression)
+                    .with_statistics(parquet.statistics)
+                    .with_row_group_size(parquet.row_group_size)
+                    .with_data_page_size(parquet.data_page_size)
                     .finish(&mut df)
                     .map_err(|e| OutputError::Io(format!("Failed to write Parquet: {}", e)))?;
             }
-            OutputFormats::Avro => {
-                AvroWriter::new(&mut file)
-                    .finish(&mut df)
-                    .map_err(|e| OutputError::Io(format!("Failed to write Arrow: {}", e)))?;
+            OutputFormats::Avro { codec } => {
+                avro::write_avro(df.lazy(), &mut file, codec.as_ref())?;
             }
             OutputFormats::Icp { compression } => {
                 let compression = compression.as_ref().map(Into::into);
@@ -160,10 +143,248 @@ pub trait OutputConnector {
                     .finish(&mut df)
                     .map_err(|e| OutputError::Io(format!("Failed to write ICP: {}", e)))?;
             }
+            format @ (OutputFormats::Table | OutputFormats::Markdown | OutputFormats::Html) => {
+                file.write_all(rendered(&df, format, &options).as_bytes())?;
+            }
         }
-        Ok(())
+        file.flush()?;
+        drop(file);
+        self.finish()
+    }
+    /// Where `write` puts the encoded rows.
+    ///
+    /// # Errors
+    /// When the file cannot be created or the output does not write to a file.
+    fn file(&self) -> Result<Box<dyn Write>, OutputError> {
+        Err(OutputError::Config(
+            "this output does not write to a file".into(),
+        ))
+    }
+}
+
+fn file_format<O: OutputConnector + ?Sized>(output: &O) -> Result<OutputFormats, OutputError> {
+    output
+        .format()
+        .ok_or_else(|| OutputError::Config("this output has no file format".into()))
+}
+
+/// Stream a frame into the output's sink target, in the output's format.
+fn sink_frame<O: OutputConnector + ?Sized>(output: &O, df: LazyFrame) -> Result<(), OutputError> {
+    let sink_options = SinkOptions {
+        sync_on_close: SyncOnCloseType::Data,
+        maintain_order: true,
+        mkdir: true,
+    };
+    // checked first, it reports configuration errors a missing target would hide
+    let cloud_options = output.cloud_options()?;
+    let format = file_format(output)?;
+    let options = output.writer_options();
+    let mut target = output.sink_target().expect("Sink target should never fail");
+    if matches!(format, OutputFormats::Json | OutputFormats::Jsonl)
+        && options.json.compression.is_some()
+    {
+        if cloud_options.is_some() {
+            return Err(OutputError::Config(
+                "Compressed Json cannot be written to cloud storage".into(),
+            ));
+        }
+        target = compressed_target(output, target, &options.json)?;
+    }
+
+    let result = match &format {
+        OutputFormats::Csv => LazyFrame::sink_csv(
+            df,
+            target,
+            options.csv.to_polars().map_err(OutputError::Config)?,
+            cloud_options,
+            sink_options,
+        )
+        .map_err(|e| OutputError::Io(format!("Failed to write CSV sink: {}", e)))?,
+        OutputFormats::Json => LazyFrame::sink_json(
+            df,
+            target,
+            JsonWriterOptions::default(),
+            cloud_options,
+            sink_options,
+        )
+        .map_err(|e| OutputError::Io(format!("Failed to write Json sink: {}", e)))?,
+        OutputFormats::Jsonl => LazyFrame::sink_json(
+            df,
+            target,
+            JsonWriterOptions {},
+            cloud_options,
+            sink_options,
+        )
+        .map_err(|e| OutputError::Io(format!("Failed to write Json sink: {}", e)))?,
+        OutputFormats::Parquet => LazyFrame::sink_parquet(
+            df,
+            target,
+            options.parquet.to_polars().map_err(OutputError::Config)?,
+            cloud_options,
+            sink_options,
+        )
+        .map_err(|e| OutputError::Io(format!("Failed to write Parquet sink: {}", e)))?,
+        OutputFormats::Avro { codec } => {
+            // written from a spooled IPC file, the sink target is not used
+            if cloud_options.is_some() {
+                return Err(OutputError::Config(
+                    "Avro cannot be written to cloud storage, use Parquet or Icp".into(),
+                ));
+            }
+            let mut file = output.file()?;
+            let written = avro::write_avro(df, &mut file, codec.as_ref());
+            drop(file);
+            return match written {
+                Ok(()) => output.finish(),
+                Err(e) => {
+                    output.discard();
+                    Err(e)
+                }
+            };
+        }
+        OutputFormats::Table | OutputFormats::Markdown | OutputFormats::Html => {
+            if cloud_options.is_some() {
+                return Err(OutputError::Config(
+                    "Table, Markdown and Html are for Stdout, Stderr and File outputs".into(),
+                ));
+            }
+            return write_rendered(output, df, &format, &options);
+        }
+        OutputFormats::Icp { compression } => LazyFrame::sink_ipc(
+            df,
+            target,
+            IpcWriterOptions {
+                compression: compression.as_ref().map(Into::into),
+                ..Default::default()
+            },
+            cloud_options,
+            sink_options,
+        )
+        .map_err(|e| OutputError::Io(format!("Failed to write ICP sink: {}", e)))?,
+    };
+    let written = result
+        .collect()
+        .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame for sink: {}", e)));
+    match written {
+        Ok(_) => output.finish(),
+        Err(e) => {
+            output.discard();
+            Err(e)
+        }
+    }
+}
+
+/// Writes the Table, Markdown and Html formats, which polars has no sink for.
+fn write_rendered<O: OutputConnector + ?Sized>(
+    output: &O,
+    df: LazyFrame,
+    format: &OutputFormats,
+    options: &WriterOptions,
+) -> Result<(), OutputError> {
+    // column widths need every row, the frame is collected and rendered in one go
+    let df = df
+        .collect()
+        .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {e}")))?;
+    let mut file = output.file()?;
+    let written = file
+        .write_all(rendered(&df, format, options).as_bytes())
+        .and_then(|()| file.flush());
+    drop(file);
+    match written {
+        Ok(()) => output.finish(),
+        Err(e) => {
+            output.discard();
+            Err(e.into())
+        }
+    }
+}
+
+/// Text of the Table, Markdown and Html formats.
+fn rendered(df: &DataFrame, format: &OutputFormats, options: &WriterOptions) -> String {
+    match format {
+        OutputFormats::Table => render::table(df, &options.table),
+        OutputFormats::Markdown => render::markdown(df),
+        OutputFormats::Html => render::html(df),
+        format => unreachable!("{format:?} is written by polars, not rendered"),
+    }
+}
+
+/// A `CsvWriter` with the configured separator, quoting, header and value formats.
+fn csv_writer<W: Write>(writer: W, config: &CsvWriterConfig) -> Result<CsvWriter<W>, OutputError> {
+    Ok(CsvWriter::new(writer)
+        .include_header(config.include_header)
+        .with_separator(config.separator().map_err(OutputError::Config)?)
+        .with_quote_style((&config.quote_style).into())
+        .with_null_value(config.null_value.clone())
+        .with_datetime_format(config.datetime_format.clone())
+        .with_date_format(config.date_format.clone())
+        .with_time_format(config.time_format.clone())
+        .with_float_precision(config.float_precision)
+        .with_float_scientific(config.float_scientific))
+}
+
+fn gzip_level(config: &JsonWriterConfig) -> Result<flate2::Compression, OutputError> {
+    match config.compression_level {
+        None => Ok(flate2::Compression::default()),
+        Some(level @ 0..=9) => Ok(flate2::Compression::new(level.unsigned_abs())),
+        Some(level) => Err(OutputError::Config(format!(
+            "gzip compression_level must be 0 to 9, not {level}"
+        ))),
     }
-    fn file(&self) -> Box<dyn Write>;
+}
+
+/// Wrap a writer in the configured Json compression. Both encoders finish the stream when they
+/// are dropped.
+fn compress_writer(
+    writer: Box<dyn Write>,
+    config: &JsonWriterConfig,
+) -> Result<Box<dyn Write>, OutputError> {
+    Ok(match config.compression {
+        None => writer,
+        Some(JsonCompressionConfig::Gzip) => Box::new(GzEncoder::new(writer, gzip_level(config)?)),
+        Some(JsonCompressionConfig::Zstd) => Box::new(
+            zstd::Encoder::new(writer, config.compression_level.unwrap_or(0))?.auto_finish(),
+        ),
+    })
+}
+
+/// Route a sink through the configured Json compression, polars' Json sinks write plain text.
+/// Files go through the output's temporary file, so they are still moved into place by
+/// `finish`.
+fn compressed_target<O: OutputConnector + ?Sized>(
+    output: &O,
+    target: SinkTarget,
+    config: &JsonWriterConfig,
+) -> Result<SinkTarget, OutputError> {
+    let inner = match (output.temp_file()?, target) {
+        (Some(file), _) => SinkWriter::File(file),
+        (None, SinkTarget::Path(path)) => {
+            return Err(OutputError::Config(format!(
+                "Compressed Json cannot be written to {}",
+                path.display()
+            )))
+        }
+        (None, SinkTarget::Dyn(writer)) => SinkWriter::Dyn(
+            writer
+                .lock()
+                .expect("sink writer lock poisoned")
+                .take()
+                .expect("sink writer is only taken once"),
+        ),
+    };
+    let writer = match config.compression {
+        Some(JsonCompressionConfig::Gzip) => {
+            CompressedWriter::Gzip(GzEncoder::new(inner, gzip_level(config)?))
+        }
+        Some(JsonCompressionConfig::Zstd) => CompressedWriter::Zstd(zstd::Encoder::new(
+            inner,
+            config.compression_level.unwrap_or(0),
+        )?),
+        None => return Err(OutputError::Config("no Json compression configured".into())),
+    };
+    Ok(SinkTarget::Dyn(SpecialEq::new(Arc::new(Mutex::new(Some(
+        Box::new(writer) as Box<dyn DynWriteable>,
+    ))))))
 }
 
 impl TryFrom<&OutputConfig> for Box<dyn OutputConnector> {
@@ -171,19 +392,35 @@ impl TryFrom<&OutputConfig> for Box<dyn OutputConnector> {
 
     fn try_from(config: &OutputConfig) -> Result<Self, Self::Error> {
         match config {
+            OutputConfig::File(file_cfg) if file_cfg.partitioning.is_partitioned() => {
+                Ok(Box::new(PartitionedOutput {
+                    target: PartitionTarget::File(Box::new(file_cfg.clone())),
+                }))
+            }
+            OutputConfig::Cloud(cloud_cfg) if cloud_cfg.partitioning.is_partitioned() => {
+                Ok(Box::new(PartitionedOutput {
+                    target: PartitionTarget::Cloud(cloud_cfg.clone()),
+                }))
+            }
             OutputConfig::File(file_cfg) => Ok(Box::new(FileOutput {
-                config: file_cfg.clone(),
+                config: FileOutputConfig {
+                    path: render_location(&file_cfg.path, None)?,
+                    ..file_cfg.clone()
+                },
             })),
             OutputConfig::Database(db_cfg) => Ok(Box::new(DatabaseOutput {
                 config: db_cfg.clone(),
             })),
             OutputConfig::Cloud(cloud_cfg) => Ok(Box::new(CloudOutput {
-                config: cloud_cfg.clone(),
+                config: CloudOutputConfig {
+                    key: render_location(&cloud_cfg.key, None)?,
+                    ..cloud_cfg.as_ref().clone()
+                },
             })),
             OutputConfig::Stdout(config) => Ok(Box::new(Stdout {
                 config: config.clone(),
             })),
-            OutputConfig::Stderr(config) => Ok(Box::new(Stdout {
+            OutputConfig::Stderr(config) => Ok(Box::new(Stderr {
                 config: config.clone(),
             })),
         }
@@ -193,8 +430,11 @@ pub struct Stderr {
     config: FormatOutputConfig,
 }
 impl OutputConnector for Stderr {
-    fn file(&self) -> Box<dyn Write> {
-        Box::new(io::stderr())
+    fn file(&self) -> Result<Box<dyn Write>, OutputError> {
+        Ok(Box::new(io::stderr()))
+    }
+    fn writer_options(&self) -> WriterOptions {
+        self.config.writer.clone()
     }
     fn stream(&self, df: LazyFrame) -> Result<(), OutputError> {
         self.write(df)
@@ -208,8 +448,8 @@ impl OutputConnector for Stderr {
         ))))))
     }
 
-    fn format(&self) -> OutputFormats {
-        self.config.format.clone()
+    fn format(&self) -> Option<OutputFormats> {
+        Some(self.config.format.clone())
     }
 }
 
@@ -217,8 +457,11 @@ pub struct Stdout {
     config: FormatOutputConfig,
 }
 impl OutputConnector for Stdout {
-    fn file(&self) -> Box<dyn Write> {
-        Box::new(io::stdout())
+    fn file(&self) -> Result<Box<dyn Write>, OutputError> {
+        Ok(Box::new(io::stdout()))
+    }
+    fn writer_options(&self) -> WriterOptions {
+        self.config.writer.clone()
     }
 
     fn sink_target(&self) -> Option<SinkTarget> {
@@ -227,25 +470,350 @@ impl OutputConnector for Stdout {
         ))))))
     }
 
-    fn format(&self) -> OutputFormats {
-        self.config.format.clone()
+    fn format(&self) -> Option<OutputFormats> {
+        Some(self.config.format.clone())
     }
 }
+/// Replace the `{date}`, `{run_id}` and `{partition}` placeholders of an output path or key.
+/// `{date}` is the current UTC date and `{run_id}` is the same for every output of a run.
+///
+/// # Errors
+/// When `template` has `{partition}` but no partition is given.
+pub fn render_location(template: &str, partition: Option<&str>) -> Result<String, OutputError> {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    let run_id = RUN_ID.get_or_init(|| uuid::Uuid::new_v4().simple().to_string());
+    let location = template
+        .replace("{date}", &chrono::Utc::now().format("%Y-%m-%d").to_string())
+        .replace("{run_id}", run_id);
+    match partition {
+        Some(partition) => Ok(location.replace("{partition}", partition)),
+        None if location.contains("{partition}") => Err(OutputError::Config(format!(
+            "{template} uses {{partition}}, which needs partition_by"
+        ))),
+        None => Ok(location),
+    }
+}
+
+/// `n`th numbered file of a path, e.g. `logs/events.2.csv` for `logs/events.csv`.
+#[must_use]
+pub fn numbered_location(location: &str, n: usize) -> String {
+    let (directory, file_name) = location
+        .rsplit_once('/')
+        .map_or(("", location), |(directory, file_name)| {
+            (directory, file_name)
+        });
+    let file_name = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{stem}.{n}.{extension}"),
+        _ => format!("{file_name}.{n}"),
+    };
+    if directory.is_empty() && !location.starts_with('/') {
+        file_name
+    } else {
+        format!("{directory}/{file_name}")
+    }
+}
+
+// rows per write when rolling by size, the size is checked between writes
+const ROLLING_BATCH_ROWS: usize = 10_000;
+
 pub struct FileOutput {
     pub config: FileOutputConfig,
 }
+impl FileOutput {
+    fn appendable(&self) -> bool {
+        matches!(
+            self.config.format,
+            OutputFormats::Csv | OutputFormats::Jsonl
+        )
+    }
+
+    fn validate(&self) -> Result<(), OutputError> {
+        if self.config.mode == FileWriteMode::Append && !self.appendable() {
+            return Err(OutputError::Config(format!(
+                "{} cannot be appended to, only Csv and Jsonl outputs can",
+                self.config.path
+            )));
+        }
+        if self.config.mode == FileWriteMode::Append
+            && self.config.writer.json.compression.is_some()
+        {
+            return Err(OutputError::Config(format!(
+                "{} is compressed and cannot be appended to",
+                self.config.path
+            )));
+        }
+        if let Some(rolling) = &self.config.rolling {
+            if rolling.max_rows.is_none() && rolling.max_bytes.is_none() {
+                return Err(OutputError::Config(
+                    "rolling needs max_rows or max_bytes".into(),
+                ));
+            }
+            if rolling.max_rows == Some(0) || rolling.max_bytes == Some(0) {
+                return Err(OutputError::Config(
+                    "rolling limits must be at least 1".into(),
+                ));
+            }
+            if rolling.max_bytes.is_some() && !self.appendable() {
+                return Err(OutputError::Config(
+                    "rolling by max_bytes is only supported for Csv and Jsonl outputs".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Outputs are written next to `path` and renamed over it once complete, so readers never
+    /// see a partial file.
+    fn temp_path(&self) -> PathBuf {
+        let path = Path::new(&self.config.path);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()))
+    }
+
+    /// Create the output directory and the temporary file the output is written to.
+    fn create_temp(&self) -> Result<File, OutputError> {
+        if let Some(parent) = Path::new(&self.config.path).parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                OutputError::Io(format!("Failed to create {} - {e}", parent.display()))
+            })?;
+        }
+        let temp_path = self.temp_path();
+        File::create(&temp_path)
+            .map_err(|e| OutputError::Io(format!("Failed to create {} - {e}", temp_path.display())))
+    }
+
+    /// A plain output for one of the numbered files of a rolling output.
+    fn numbered(&self, n: usize, mode: FileWriteMode) -> FileOutput {
+        FileOutput {
+            config: FileOutputConfig {
+                path: numbered_location(&self.config.path, n),
+                mode,
+                rolling: None,
+                ..self.config.clone()
+            },
+        }
+    }
+
+    /// Rows already in a Csv or Jsonl file. Counted by lines, so quoted newlines in Csv values
+    /// make it an overestimate.
+    fn existing_rows(&self) -> Result<usize, OutputError> {
+        let lines = BufReader::new(File::open(&self.config.path)?)
+            .lines()
+            .count();
+        Ok(match self.config.format {
+            OutputFormats::Csv if self.config.writer.csv.include_header => lines.saturating_sub(1),
+            _ => lines,
+        })
+    }
+
+    /// Add rows to the end of an existing file. The Csv header is only written when the file is
+    /// created, and has to match the header of the new rows.
+    fn append(&self, df: LazyFrame) -> Result<(), OutputError> {
+        let mut df = df
+            .collect()
+            .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {e}")))?;
+        let mut file = OpenOptions::new().append(true).open(&self.config.path)?;
+        match self.config.format {
+            OutputFormats::Csv => {
+                let csv = &self.config.writer.csv;
+                if csv.include_header {
+                    let mut header = Vec::new();
+                    csv_writer(&mut header, csv)?
+                        .finish(&mut df.clear())
+                        .map_err(|e| OutputError::Io(format!("Failed to write CSV: {e}")))?;
+                    let header = String::from_utf8_lossy(&header);
+                    let existing = BufReader::new(File::open(&self.config.path)?)
+                        .lines()
+                        .next()
+                        .transpose()?
+                        .unwrap_or_default();
+                    if existing != header.trim_end() {
+                        return Err(OutputError::Config(format!(
+                            "Cannot append to {}, its header {existing} does not match {}",
+                            self.config.path,
+                            header.trim_end()
+                        )));
+                    }
+                }
+                csv_writer(&mut file, csv)?
+                    .include_header(false)
+                    .finish(&mut df)
+                    .map_err(|e| OutputError::Io(format!("Failed to write CSV: {e}")))?;
+            }
+            OutputFormats::Jsonl => {
+                JsonWriter::new(&mut file)
+                    .with_json_format(JsonFormat::JsonLines)
+                    .finish(&mut df)
+                    .map_err(|e| OutputError::Io(format!("Failed to write JSONLine: {e}")))?;
+            }
+            _ => unreachable!("append is validated to Csv and Jsonl outputs"),
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    fn write_file(&self, df: LazyFrame) -> Result<(), OutputError> {
+        let path = Path::new(&self.config.path);
+        match self.config.mode {
+            FileWriteMode::ErrorIfExists if path.exists() => Err(OutputError::Io(format!(
+                "{} already exists",
+                self.config.path
+            ))),
+            FileWriteMode::Append if path.metadata().is_ok_and(|meta| meta.len() > 0) => {
+                self.append(df)
+            }
+            _ => sink_frame(self, df),
+        }
+    }
+
+    /// Numbered files of a rolling output already on disk.
+    fn numbered_files(&self) -> usize {
+        let mut count = 0;
+        while Path::new(&numbered_location(&self.config.path, count)).exists() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Remove the first `count` numbered files.
+    fn remove_numbered(&self, count: usize) -> Result<(), OutputError> {
+        for n in 0..count {
+            std::fs::remove_file(numbered_location(&self.config.path, n))?;
+        }
+        Ok(())
+    }
+
+    /// Write the numbered files next to the `existing` ones and only swap them in once all are
+    /// written, so a failed write leaves the earlier files in place.
+    fn replace_rolling(
+        &self,
+        df: LazyFrame,
+        rolling: &RollingConfig,
+        existing: usize,
+    ) -> Result<(), OutputError> {
+        let staged = FileOutput {
+            config: FileOutputConfig {
+                path: self.temp_path().to_string_lossy().into_owned(),
+                mode: FileWriteMode::Overwrite,
+                ..self.config.clone()
+            },
+        };
+        let _ = staged.remove_numbered(staged.numbered_files());
+        if let Err(e) = staged.write_rolling(df, rolling) {
+            let _ = staged.remove_numbered(staged.numbered_files());
+            return Err(e);
+        }
+        self.remove_numbered(existing)?;
+        for n in 0..staged.numbered_files() {
+            std::fs::rename(
+                numbered_location(&staged.config.path, n),
+                numbered_location(&self.config.path, n),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Fill the last numbered file up to the rolling limits, then continue in new ones.
+    fn write_rolling(&self, df: LazyFrame, rolling: &RollingConfig) -> Result<(), OutputError> {
+        let existing = self.numbered_files();
+        let (mut part, mut rows, mut bytes) = match self.config.mode {
+            FileWriteMode::ErrorIfExists if existing > 0 => {
+                return Err(OutputError::Io(format!(
+                    "{} already exists",
+                    numbered_location(&self.config.path, 0)
+                )))
+            }
+            FileWriteMode::Append if existing > 0 => {
+                let last = self.numbered(existing - 1, FileWriteMode::Append);
+                let bytes = std::fs::metadata(&last.config.path)?.len();
+                (existing - 1, last.existing_rows()?, bytes)
+            }
+            FileWriteMode::Overwrite if existing > 0 => {
+                return self.replace_rolling(df, rolling, existing)
+            }
+            _ => (0, 0, 0),
+        };
+        let df = df
+            .collect()
+            .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {e}")))?;
+        let batch_rows = if rolling.max_bytes.is_some() {
+            ROLLING_BATCH_ROWS
+        } else {
+            df.height().max(1)
+        };
+        let mut offset = 0;
+        loop {
+            let full = rolling.max_rows.is_some_and(|max| rows >= max)
+                || rolling.max_bytes.is_some_and(|max| bytes >= max);
+            if full && offset < df.height() {
+                (part, rows) = (part + 1, 0);
+            }
+            let capacity = rolling
+                .max_rows
+                .map_or(usize::MAX, |max| max.saturating_sub(rows));
+            let len = capacity.min(batch_rows).min(df.height() - offset);
+            let output = self.numbered(part, FileWriteMode::Append);
+            output.write_file(
+                df.slice(i64::try_from(offset).expect("row offsets fit in i64"), len)
+                    .lazy(),
+            )?;
+            rows += len;
+            bytes = std::fs::metadata(&output.config.path)?.len();
+            offset += len;
+            if offset >= df.height() {
+                return Ok(());
+            }
+        }
+    }
+}
+
 impl OutputConnector for FileOutput {
-    fn file(&self) -> Box<dyn Write> {
-        Box::new(File::create(self.config.path.clone()).expect("could not create file"))
+    fn stream(&self, df: LazyFrame) -> Result<(), OutputError> {
+        self.validate()?;
+        match &self.config.rolling {
+            Some(rolling) => self.write_rolling(df, rolling),
+            None => self.write_file(df),
+        }
+    }
+
+    fn write(&self, df: LazyFrame) -> Result<(), OutputError> {
+        self.stream(df)
+    }
+
+    fn writer_options(&self) -> WriterOptions {
+        self.config.writer.clone()
+    }
+
+    fn file(&self) -> Result<Box<dyn Write>, OutputError> {
+        Ok(Box::new(self.create_temp()?))
+    }
+
+    fn temp_file(&self) -> Result<Option<File>, OutputError> {
+        self.create_temp().map(Some)
     }
 
     fn sink_target(&self) -> Option<SinkTarget> {
-        Some(SinkTarget::Dyn(SpecialEq::new(Arc::new(Mutex::new(Some(
-            Box::new(StdWriter::stderr()) as Box<dyn polars_io::utils::file::DynWriteable>,
-        ))))))
+        Some(SinkTarget::Path(Arc::new(self.temp_path())))
+    }
+
+    fn finish(&self) -> Result<(), OutputError> {
+        std::fs::rename(self.temp_path(), &self.config.path).map_err(|e| {
+            OutputError::Io(format!(
+                "Failed to move output into {} - {e}",
+                self.config.path
+            ))
+        })
     }
-    fn format(&self) -> OutputFormats {
-        self.config.format.clone()
+
+    fn discard(&self) {
+        let _ = std::fs::remove_file(self.temp_path());
+    }
+
+    fn format(&self) -> Option<OutputFormats> {
+        Some(self.config.format.clone())
     }
 }
 
@@ -253,16 +821,16 @@ pub struct DatabaseOutput {
     pub config: DatabaseOutputConfig,
 }
 impl OutputConnector for DatabaseOutput {
-    fn write(&self, _df: LazyFrame) -> Result<(), OutputError> {
-        // Implement database writing logic here
-        Ok(())
-    }
-    fn file(&self) -> Box<dyn Write> {
-        todo!()
+    // rows go through a database client, there is no sink to stream into
+    fn stream(&self, df: LazyFrame) -> Result<(), OutputError> {
+        self.write(df)
     }
 
-    fn format(&self) -> OutputFormats {
-        todo!()
+    fn write(&self, df: LazyFrame) -> Result<(), OutputError> {
+        let df = df
+            .collect()
+            .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {e}")))?;
+        database::write_database(&df, &self.config)
     }
 }
 
@@ -270,17 +838,252 @@ pub struct CloudOutput {
     pub config: CloudOutputConfig,
 }
 impl OutputConnector for CloudOutput {
-    fn write(&self, _df: LazyFrame) -> Result<(), OutputError> {
-        //CloudWriter
-        // Implement cloud writing logic here
+    // polars' cloud sinks upload in parts as the data streams, so there is no local file
+    fn write(&self, df: LazyFrame) -> Result<(), OutputError> {
+        self.stream(df)
+    }
+
+    fn writer_options(&self) -> WriterOptions {
+        self.config.writer.clone()
+    }
+
+    fn sink_target(&self) -> Option<SinkTarget> {
+        self.config
+            .url()
+            .ok()
+            .map(|url| SinkTarget::Path(Arc::new(PathBuf::from(url))))
+    }
+
+    fn cloud_options(&self) -> Result<Option<CloudOptions>, OutputError> {
+        let url = self.config.url().map_err(OutputError::Config)?;
+        let cloud_options = self
+            .config
+            .cloud
+            .to_cloud_options(&url)
+            .map_err(OutputError::Config)?;
+        Ok(Some(cloud_options))
+    }
+
+    fn format(&self) -> Option<OutputFormats> {
+        Some(self.config.format.clone())
+    }
+}
+
+pub enum PartitionTarget {
+    File(Box<FileOutputConfig>),
+    Cloud(Box<CloudOutputConfig>),
+}
+
+/// Writes a hive style dataset under the file path or cloud key, one output per part file.
+pub struct PartitionedOutput {
+    pub target: PartitionTarget,
+}
+
+/// Hive directory value. Nulls get hive's default partition name and characters that are not
+/// safe in paths are percent encoded, as polars' hive reader expects.
+fn partition_value(value: &AnyValue) -> String {
+    let value = match value {
+        AnyValue::Null => return "__HIVE_DEFAULT_PARTITION__".to_string(),
+        value => value
+            .get_str()
+            .map_or_else(|| value.to_string(), str::to_string),
+    };
+    value
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+                char::from(byte).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+/// Split a frame into `(location, rows)` parts. Partition columns become directories and are
+/// left out of the files. The directories go where `template` has `{partition}`, otherwise the
+/// template is the dataset root and parts are named `part-N`.
+///
+/// # Errors
+/// When a partition column is missing, `max_rows_per_file` is 0 or `template` cannot be
+/// rendered.
+pub fn partition_parts(
+    df: &DataFrame,
+    config: &PartitionConfig,
+    template: &str,
+    extension: &str,
+) -> Result<Vec<(String, DataFrame)>, OutputError> {
+    if let Some(missing) = config
+        .partition_by
+        .iter()
+        .find(|column| df.schema().get(column.as_str()).is_none())
+    {
+        return Err(OutputError::Config(format!(
+            "partition column {missing} is not in the output"
+        )));
+    }
+    if config.max_rows_per_file == Some(0) {
+        return Err(OutputError::Config(
+            "max_rows_per_file must be at least 1".into(),
+        ));
+    }
+    let groups = if config.partition_by.is_empty() {
+        vec![df.clone()]
+    } else {
+        df.partition_by_stable(config.partition_by.iter().map(String::as_str), true)
+            .map_err(|e| OutputError::Other(format!("Failed to partition output - {e}")))?
+    };
+
+    let mut parts = Vec::new();
+    for group in groups {
+        let mut directory = String::new();
+        for column in &config.partition_by {
+            let value = group
+                .column(column)
+                .and_then(|values| values.get(0))
+                .map_err(|e| OutputError::Other(format!("Failed to read partition value - {e}")))?;
+            let _ = write!(directory, "{column}={}/", partition_value(&value));
+        }
+        let group = group.drop_many(config.partition_by.iter().map(String::as_str));
+        let rows_per_file = config.max_rows_per_file.unwrap_or(group.height()).max(1);
+        for (index, offset) in (0..group.height().max(1))
+            .step_by(rows_per_file)
+            .enumerate()
+        {
+            let location = if template.contains("{partition}") {
+                let partition =
+                    (!config.partition_by.is_empty()).then(|| directory.trim_end_matches('/'));
+                let location = render_location(template, partition)?;
+                match config.max_rows_per_file {
+                    Some(_) => numbered_location(&location, index),
+                    None => location,
+                }
+            } else {
+                let root = render_location(template, None)?;
+                format!(
+                    "{}/{directory}part-{index}.{extension}",
+                    root.trim_end_matches('/')
+                )
+            };
+            let offset = i64::try_from(offset)
+                .map_err(|e| OutputError::Other(format!("Row offset out of range - {e}")))?;
+            parts.push((location, group.slice(offset, rows_per_file)));
+        }
+    }
+    Ok(parts)
+}
+
+impl PartitionedOutput {
+    fn write_parts(&self, df: &DataFrame, template: &str) -> Result<(), OutputError> {
+        let (partitioning, extension) = match &self.target {
+            PartitionTarget::File(config) => (&config.partitioning, config.format.extension()),
+            PartitionTarget::Cloud(config) => (&config.partitioning, config.format.extension()),
+        };
+        for (location, part) in partition_parts(df, partitioning, template, extension)? {
+            let output: Box<dyn OutputConnector> = match &self.target {
+                PartitionTarget::File(config) => Box::new(FileOutput {
+                    config: FileOutputConfig {
+                        path: location,
+                        partitioning: PartitionConfig::default(),
+                        ..config.as_ref().clone()
+                    },
+                }),
+                PartitionTarget::Cloud(config) => Box::new(CloudOutput {
+                    config: CloudOutputConfig {
+                        key: location,
+                        partitioning: PartitionConfig::default(),
+                        ..config.as_ref().clone()
+                    },
+                }),
+            };
+            output.stream(part.lazy())?;
+        }
         Ok(())
     }
-    fn file(&self) -> Box<dyn Write> {
-        todo!()
+
+    /// Write a local dataset in the output's mode. The files of a `{partition}` path check the
+    /// mode one by one, apart from Overwrite, which replaces the dataset as a whole.
+    fn write_dataset(&self, df: &DataFrame, config: &FileOutputConfig) -> Result<(), OutputError> {
+        match config.mode {
+            FileWriteMode::Overwrite => self.replace_dataset(df, &config.path),
+            FileWriteMode::ErrorIfExists if !config.path.contains("{partition}") => {
+                let root = PathBuf::from(render_location(&config.path, None)?);
+                if root
+                    .read_dir()
+                    .is_ok_and(|mut entries| entries.next().is_some())
+                {
+                    return Err(OutputError::Io(format!(
+                        "{} already exists",
+                        root.display()
+                    )));
+                }
+                self.write_parts(df, &config.path)
+            }
+            _ => self.write_parts(df, &config.path),
+        }
+    }
+
+    /// Build the new dataset next to its root and swap it in, so parts of an earlier run never
+    /// linger and a failed write leaves the old dataset in place. The root of a `{partition}`
+    /// path is the directory holding `{partition}`.
+    fn replace_dataset(&self, df: &DataFrame, template: &str) -> Result<(), OutputError> {
+        let (root, rest) = match template.find("{partition}") {
+            Some(index) => {
+                let slash = template[..index].rfind('/').ok_or_else(|| {
+                    OutputError::Config(format!(
+                        "{template} needs a directory around {{partition}} to be overwritten"
+                    ))
+                })?;
+                template.split_at(slash)
+            }
+            None => (template, ""),
+        };
+        let root = PathBuf::from(render_location(root, None)?);
+        let file_name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let staging = root.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+        let _ = std::fs::remove_dir_all(&staging);
+        if let Err(e) = self.write_parts(df, &format!("{}{rest}", staging.display())) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+        if root.exists() {
+            std::fs::remove_dir_all(&root).map_err(|e| {
+                OutputError::Io(format!("Failed to replace {} - {e}", root.display()))
+            })?;
+        }
+        std::fs::rename(&staging, &root).map_err(|e| {
+            OutputError::Io(format!(
+                "Failed to move dataset into {} - {e}",
+                root.display()
+            ))
+        })
+    }
+}
+
+impl OutputConnector for PartitionedOutput {
+    fn stream(&self, df: LazyFrame) -> Result<(), OutputError> {
+        self.write(df)
+    }
+
+    // grouping needs every row, the parts are streamed from the collected frame
+    fn write(&self, df: LazyFrame) -> Result<(), OutputError> {
+        let df = df
+            .collect()
+            .map_err(|e| OutputError::Io(format!("Failed to collect DataFrame: {e}")))?;
+        match &self.target {
+            PartitionTarget::File(config) => self.write_dataset(&df, config),
+            PartitionTarget::Cloud(config) => self.write_parts(&df, &config.key),
+        }
     }
 
-    fn format(&self) -> OutputFormats {
-        todo!()
+    fn format(&self) -> Option<OutputFormats> {
+        Some(match &self.target {
+            PartitionTarget::File(config) => config.format.clone(),
+            PartitionTarget::Cloud(config) => config.format.clone(),
+        })
     }
 }
 
@@ -341,3 +1144,79 @@ impl io::Write for StdWriter {
         }
     }
 }
+
+/// What a compressed sink writes into, a file or the writer of the original sink target.
+enum SinkWriter {
+    File(File),
+    Dyn(Box<dyn DynWriteable>),
+}
+
+impl SinkWriter {
+    fn close(self) -> io::Result<()> {
+        match self {
+            SinkWriter::File(file) => file.sync_data(),
+            SinkWriter::Dyn(writer) => writer.close(),
+        }
+    }
+}
+
+impl io::Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SinkWriter::File(file) => file.write(buf),
+            SinkWriter::Dyn(writer) => writer.as_mut_dyn_write().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SinkWriter::File(file) => file.flush(),
+            SinkWriter::Dyn(writer) => writer.as_mut_dyn_write().flush(),
+        }
+    }
+}
+
+/// Gzip or zstd around a sink, the compressed stream is finished when polars closes the sink.
+enum CompressedWriter {
+    Gzip(GzEncoder<SinkWriter>),
+    Zstd(zstd::Encoder<'static, SinkWriter>),
+}
+
+impl DynWriteable for CompressedWriter {
+    fn as_dyn_write(&self) -> &(dyn io::Write + Send + 'static) {
+        self
+    }
+
+    fn as_mut_dyn_write(&mut self) -> &mut (dyn io::Write + Send + 'static) {
+        self
+    }
+
+    fn close(self: Box<Self>) -> io::Result<()> {
+        let inner = match *self {
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        inner.close()
+    }
+
+    // files are synced once the compressed stream is complete, in close
+    fn sync_on_close(&mut self, _sync_on_close: SyncOnCloseType) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,502 @@
+use crate::configs::{input::InputFormat, schema::SchemaDtype};
+use polars::prelude::*;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// Turns one line of text into named string values. Values are typed afterwards with
+/// `dtypes` so every parser shares the same column building.
+pub trait LineParser {
+    /// Returns None when the line does not match the format.
+    fn parse_line(&self, line: &str) -> Option<Vec<(String, Option<String>)>>;
+
+    /// Dtypes for known columns, anything not listed stays a string.
+    fn dtypes(&self) -> Vec<(String, DataType)> {
+        Vec::new()
+    }
+
+    /// Columns computed from the parsed ones, e.g. `is_error` from `status_code`. `schema`
+    /// is the parsed frame's, for formats whose columns vary from line to line.
+    fn derived_columns(&self, _schema: &Schema) -> Vec<Expr> {
+        Vec::new()
+    }
+
+    /// Whether untyped string columns should be given a dtype from their values.
+    fn infers_dtypes(&self) -> bool {
+        false
+    }
+}
+
+/// The parser for line based input formats, None for formats polars reads itself.
+///
+/// # Errors
+/// When a Regex input has an invalid pattern.
+pub fn line_parser(format: &InputFormat) -> Result<Option<Box<dyn LineParser>>, String> {
+    let parser: Box<dyn LineParser> = match format {
+        InputFormat::NginxCombined => Box::new(AccessLogParser::nginx_combined()),
+        InputFormat::ApacheCommon => Box::new(AccessLogParser::apache_common()),
+        InputFormat::Logfmt => Box::new(LogfmtParser),
+        InputFormat::Syslog => Box::new(SyslogParser::new()),
+        InputFormat::Regex {
+            pattern, dtypes, ..
+        } => Box::new(RegexParser::new(pattern, dtypes)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(parser))
+}
+
+pub struct ParsedLines {
+    pub df: DataFrame,
+    pub rejected: usize,
+}
+
+/// String columns built up one record at a time. Columns first seen part way through are
+/// back filled with nulls.
+#[derive(Default)]
+struct RecordColumns {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    values: Vec<Vec<Option<String>>>,
+    rows: usize,
+}
+
+impl RecordColumns {
+    fn push(&mut self, record: Vec<(String, Option<String>)>) {
+        for (name, value) in record {
+            let position = if let Some(position) = self.index.get(&name) {
+                *position
+            } else {
+                self.index.insert(name.clone(), self.names.len());
+                self.names.push(name);
+                self.values.push(vec![None; self.rows]);
+                self.values.len() - 1
+            };
+            // a repeated key keeps the last value
+            if self.values[position].len() > self.rows {
+                self.values[position][self.rows] = value;
+            } else {
+                self.values[position].push(value);
+            }
+        }
+        self.rows += 1;
+        for values in &mut self.values {
+            values.resize(self.rows, None);
+        }
+    }
+
+    fn into_dataframe(self) -> PolarsResult<DataFrame> {
+        let columns = self
+            .names
+            .into_iter()
+            .zip(self.values)
+            .map(|(name, values)| Column::new(name.into(), values))
+            .collect();
+        DataFrame::new(columns)
+    }
+}
+
+/// Parse every line of a local file. Lines that do not match are counted and, when a
+/// reject writer is given, copied there unchanged.
+///
+/// # Errors
+/// When the file cannot be read or a rejected line cannot be written.
+pub fn parse_file(
+    parser: &dyn LineParser,
+    path: &Path,
+    mut rejects: Option<&mut dyn Write>,
+) -> Result<ParsedLines, String> {
+    let file = File::open(path).map_err(|e| format!("Error opening {} - {e}", path.display()))?;
+    let mut records = RecordColumns::default();
+    let mut rejected = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Error reading {} - {e}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(record) = parser.parse_line(&line) {
+            records.push(record);
+        } else {
+            rejected += 1;
+            if let Some(rejects) = rejects.as_mut() {
+                writeln!(rejects, "{line}")
+                    .map_err(|e| format!("Error writing rejected line - {e}"))?;
+            }
+        }
+    }
+    let df = records
+        .into_dataframe()
+        .map_err(|e| format!("Error building frame from {} - {e}", path.display()))?;
+    Ok(ParsedLines { df, rejected })
+}
+
+/// Cast the parsed string columns and add the derived ones.
+///
+/// # Errors
+/// When the dtypes of untyped columns cannot be inferred.
+pub fn typed_frame(parser: &dyn LineParser, df: DataFrame) -> PolarsResult<LazyFrame> {
+    let df = if parser.infers_dtypes() {
+        infer_dtypes(&df)?
+    } else {
+        df
+    };
+    let schema = df.schema().clone();
+    let casts: Vec<Expr> = parser
+        .dtypes()
+        .into_iter()
+        .filter(|(name, _)| schema.contains(name))
+        .map(|(name, dtype)| col(name.as_str()).cast(dtype))
+        .collect();
+    let mut df = df.lazy().with_columns(casts);
+    let derived = parser.derived_columns(&schema);
+    if !derived.is_empty() {
+        df = df.with_columns(derived);
+    }
+    Ok(df)
+}
+
+fn dash_is_null(value: &str) -> Option<String> {
+    (value != "-").then(|| value.to_string())
+}
+
+// 10/Oct/2000:13:55:36 -0700 -> 2000-10-10T13:55:36-07:00, the log_generator format
+fn clf_timestamp(value: &str) -> Option<String> {
+    chrono::DateTime::parse_from_str(value, "%d/%b/%Y:%H:%M:%S %z")
+        .ok()
+        .map(|timestamp| timestamp.to_rfc3339())
+}
+
+/// nginx `combined` and Apache `common`/`combined` access logs.
+pub struct AccessLogParser {
+    pattern: Regex,
+}
+
+impl AccessLogParser {
+    #[must_use]
+    pub fn nginx_combined() -> Self {
+        Self::new(concat!(
+            r#"^(?P<client_ip>\S+) \S+ (?P<user_id>\S+) \[(?P<timestamp>[^\]]+)\] "#,
+            r#""(?P<method>[A-Z]+) (?P<endpoint>\S+)(?: (?P<protocol>[^"]*))?" "#,
+            r#"(?P<status_code>\d{3}) (?P<response_size_bytes>\d+|-) "#,
+            r#""(?P<referer>[^"]*)" "(?P<user_agent>[^"]*)""#,
+            // many setups append $request_time
+            r#"(?: (?P<request_time>\d+(?:\.\d+)?))?"#,
+        ))
+    }
+
+    #[must_use]
+    pub fn apache_common() -> Self {
+        Self::new(concat!(
+            r#"^(?P<client_ip>\S+) \S+ (?P<user_id>\S+) \[(?P<timestamp>[^\]]+)\] "#,
+            r#""(?P<method>[A-Z]+) (?P<endpoint>\S+)(?: (?P<protocol>[^"]*))?" "#,
+            r#"(?P<status_code>\d{3}) (?P<response_size_bytes>\d+|-)"#,
+        ))
+    }
+
+    fn new(pattern: &str) -> Self {
+        Self {
+            pattern: Regex::new(pattern).expect("access log pattern is valid"),
+        }
+    }
+}
+
+impl LineParser for AccessLogParser {
+    fn parse_line(&self, line: &str) -> Option<Vec<(String, Option<String>)>> {
+        let captures = self.pattern.captures(line)?;
+        let mut record = Vec::with_capacity(12);
+        for name in self.pattern.capture_names().flatten() {
+            let Some(value) = captures.name(name).map(|value| value.as_str()) else {
+                continue;
+            };
+            match name {
+                "timestamp" => record.push((name.to_string(), clf_timestamp(value))),
+                // seconds with millisecond resolution, reported like the generator's field
+                "request_time" => record.push((
+                    "response_time_ms".to_string(),
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .map(|seconds| (seconds * 1000.0).round().to_string()),
+                )),
+                name => record.push((name.to_string(), dash_is_null(value))),
+            }
+        }
+        // access logs carry no request id, the column is kept null so pipelines that reference
+        // it still resolve
+        record.push(("request_id".to_string(), None));
+        Some(record)
+    }
+
+    fn dtypes(&self) -> Vec<(String, DataType)> {
+        vec![
+            ("status_code".to_string(), DataType::Int64),
+            ("response_size_bytes".to_string(), DataType::Int64),
+            ("response_time_ms".to_string(), DataType::Int64),
+        ]
+    }
+
+    fn derived_columns(&self, _schema: &Schema) -> Vec<Expr> {
+        vec![col("status_code").gt_eq(lit(400)).alias("is_error")]
+    }
+}
+
+/// RFC 5424 syslog messages. `facility` and `severity` are split out of the priority and
+/// nil values (`-`) become nulls.
+pub struct SyslogParser {
+    pattern: Regex,
+}
+
+impl SyslogParser {
+    /// # Panics
+    /// Never, the built in pattern is valid.
+    #[must_use]
+    pub fn new() -> Self {
+        let pattern = concat!(
+            r#"^<(?P<priority>\d{1,3})>(?P<version>\d{1,2}) (?P<timestamp>\S+) "#,
+            r#"(?P<hostname>\S+) (?P<app_name>\S+) (?P<procid>\S+) (?P<msgid>\S+) "#,
+            r#"(?P<structured_data>-|(?:\[(?:[^\]\\]|\\.)*\])+)(?: (?P<message>.*))?$"#,
+        );
+        Self {
+            pattern: Regex::new(pattern).expect("syslog pattern is valid"),
+        }
+    }
+}
+
+impl Default for SyslogParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineParser for SyslogParser {
+    fn parse_line(&self, line: &str) -> Option<Vec<(String, Option<String>)>> {
+        let captures = self.pattern.captures(line.trim_end_matches('\r'))?;
+        let record = self
+            .pattern
+            .capture_names()
+            .flatten()
+            .map(|name| {
+                let value = captures.name(name).and_then(|value| match name {
+                    // a utf-8 message may start with a byte order mark
+                    "message" => Some(value.as_str().trim_start_matches('\u{feff}').to_string()),
+                    _ => dash_is_null(value.as_str()),
+                });
+                (name.to_string(), value)
+            })
+            .collect();
+        Some(record)
+    }
+
+    fn dtypes(&self) -> Vec<(String, DataType)> {
+        vec![
+            ("priority".to_string(), DataType::Int64),
+            ("version".to_string(), DataType::Int64),
+        ]
+    }
+
+    fn derived_columns(&self, _schema: &Schema) -> Vec<Expr> {
+        vec![
+            col("priority").floor_div(lit(8)).alias("facility"),
+            (col("priority") % lit(8)).alias("severity"),
+        ]
+    }
+}
+
+/// `key=value` lines as written by logfmt and most Go loggers.
+pub struct LogfmtParser;
+
+// common logfmt keys renamed to the columns the log_generator writes
+const LOGFMT_ALIASES: &[(&str, &str)] = &[
+    ("time", "timestamp"),
+    ("ts", "timestamp"),
+    ("path", "endpoint"),
+    ("uri", "endpoint"),
+    ("status", "status_code"),
+    ("remote_addr", "client_ip"),
+    ("ip", "client_ip"),
+    ("ua", "user_agent"),
+    ("service", "service_name"),
+    ("duration_ms", "response_time_ms"),
+    ("latency_ms", "response_time_ms"),
+];
+
+impl LogfmtParser {
+    fn pairs(line: &str) -> Option<Vec<(String, Option<String>)>> {
+        let mut pairs = Vec::new();
+        let mut chars = line.trim().chars().peekable();
+        while chars.peek().is_some() {
+            let mut key = String::new();
+            let mut has_value = false;
+            for c in chars.by_ref() {
+                match c {
+                    '=' => {
+                        has_value = true;
+                        break;
+                    }
+                    c if c.is_whitespace() => break,
+                    c => key.push(c),
+                }
+            }
+            if key.is_empty() {
+                continue;
+            }
+            // a bare key is a flag without a value
+            if !has_value {
+                pairs.push((key, None));
+                continue;
+            }
+            let mut value = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match (escaped, c) {
+                        (false, '\\') => escaped = true,
+                        (false, '"') => break,
+                        (_, c) => {
+                            escaped = false;
+                            value.push(c);
+                        }
+                    }
+                }
+                // skip the separator after a quoted value
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            } else {
+                value = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+            }
+            pairs.push((key, (!value.is_empty()).then_some(value)));
+        }
+        // free text without a single key=value pair is not logfmt
+        pairs
+            .iter()
+            .any(|(_, value)| value.is_some())
+            .then_some(pairs)
+    }
+}
+
+impl LineParser for LogfmtParser {
+    fn parse_line(&self, line: &str) -> Option<Vec<(String, Option<String>)>> {
+        let mut pairs = Self::pairs(line)?;
+        for (key, target) in LOGFMT_ALIASES {
+            let has_target = pairs.iter().any(|(name, _)| name == target);
+            if let Some(pair) = pairs.iter_mut().find(|(name, _)| name == key) {
+                if !has_target {
+                    pair.0 = target.to_string();
+                }
+            }
+        }
+        Some(pairs)
+    }
+
+    fn derived_columns(&self, schema: &Schema) -> Vec<Expr> {
+        if !schema.contains("status_code") || schema.contains("is_error") {
+            return Vec::new();
+        }
+        // status values that are not numbers give a null is_error
+        vec![col("status_code")
+            .cast(DataType::Int64)
+            .gt_eq(lit(400))
+            .alias("is_error")]
+    }
+
+    fn infers_dtypes(&self) -> bool {
+        true
+    }
+}
+
+/// Lines matched against a user supplied pattern, one column per named capture group.
+pub struct RegexParser {
+    pattern: Regex,
+    dtypes: Vec<(String, DataType)>,
+}
+
+impl RegexParser {
+    /// A parser for `pattern`, with `dtypes` for some of its named groups.
+    ///
+    /// # Errors
+    /// When the pattern is invalid, has no named groups or `dtypes` names a group it lacks.
+    pub fn new(pattern: &str, dtypes: &HashMap<String, SchemaDtype>) -> Result<Self, String> {
+        let pattern =
+            Regex::new(pattern).map_err(|e| format!("Invalid regex input pattern - {e}"))?;
+        let names: Vec<&str> = pattern.capture_names().flatten().collect();
+        if names.is_empty() {
+            return Err(
+                "Regex input pattern needs at least one named group, e.g. (?P<status>\\d+)".into(),
+            );
+        }
+        if let Some(unknown) = dtypes.keys().find(|name| !names.contains(&name.as_str())) {
+            return Err(format!(
+                "Regex input dtype given for {unknown}, which is not a named group of the pattern"
+            ));
+        }
+        let dtypes: Vec<(String, DataType)> = dtypes
+            .iter()
+            .map(|(name, dtype)| (name.clone(), dtype.into()))
+            .collect();
+        Ok(Self { pattern, dtypes })
+    }
+}
+
+impl LineParser for RegexParser {
+    fn parse_line(&self, line: &str) -> Option<Vec<(String, Option<String>)>> {
+        let captures = self.pattern.captures(line)?;
+        Some(
+            self.pattern
+                .capture_names()
+                .flatten()
+                .map(|name| {
+                    let value = captures.name(name).map(|value| value.as_str().to_string());
+                    (name.to_string(), value)
+                })
+                .collect(),
+        )
+    }
+
+    fn dtypes(&self) -> Vec<(String, DataType)> {
+        self.dtypes.clone()
+    }
+}
+
+/// Give string columns a numeric or boolean dtype when every value parses as one. Used for
+/// formats like logfmt where the values carry no type information.
+///
+/// # Errors
+/// When a column cannot be cast to the dtype its values parse as.
+pub fn infer_dtypes(df: &DataFrame) -> PolarsResult<DataFrame> {
+    let mut columns = Vec::with_capacity(df.width());
+    for column in df.get_columns() {
+        let Ok(values) = column.str() else {
+            columns.push(column.clone());
+            continue;
+        };
+        let non_null: Vec<&str> = values.into_iter().flatten().collect();
+        let dtype = if non_null.is_empty() {
+            None
+        } else if non_null.iter().all(|v| v.parse::<i64>().is_ok()) {
+            Some(DataType::Int64)
+        } else if non_null.iter().all(|v| v.parse::<f64>().is_ok()) {
+            Some(DataType::Float64)
+        } else if non_null.iter().all(|v| *v == "true" || *v == "false") {
+            Some(DataType::Boolean)
+        } else {
+            None
+        };
+        match dtype {
+            Some(DataType::Boolean) => {
+                let flags: BooleanChunked = values
+                    .into_iter()
+                    .map(|value| value.map(|value| value == "true"))
+                    .collect();
+                columns.push(flags.with_name(column.name().clone()).into_column());
+            }
+            Some(dtype) => columns.push(column.cast(&dtype)?),
+            None => columns.push(column.clone()),
+        }
+    }
+    DataFrame::new(columns)
+}
@@ -0,0 +1,165 @@
+//! Human readable renderings of a frame: an aligned terminal table, a Markdown table and a
+//! standalone HTML page.
+use crate::configs::output::TableConfig;
+use polars::prelude::*;
+use std::fmt::Write as _;
+
+/// Cell text, None for nulls so every rendering can show them its own way.
+fn cell(column: &Column, row: usize) -> Option<String> {
+    match column.get(row).unwrap_or(AnyValue::Null) {
+        AnyValue::Null => None,
+        value => Some(
+            value
+                .get_str()
+                .map_or_else(|| value.to_string(), str::to_string),
+        ),
+    }
+}
+
+fn is_numeric(column: &Column) -> bool {
+    column.dtype().is_primitive_numeric()
+}
+
+fn truncate(value: &str, max_width: usize) -> String {
+    // newlines would break the row layout
+    let value = value.replace('\n', "\\n");
+    if value.chars().count() <= max_width {
+        value
+    } else {
+        let mut truncated: String = value.chars().take(max_width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Columns padded to a common width, numbers right aligned. Values wider than
+/// `max_column_width` are cut short and only the first `max_rows` rows are shown.
+pub fn table(df: &DataFrame, config: &TableConfig) -> String {
+    let shown = df.height().min(config.max_rows);
+    let columns: Vec<(Vec<String>, bool)> = df
+        .get_columns()
+        .iter()
+        .map(|column| {
+            let mut cells = vec![
+                truncate(column.name(), config.max_column_width),
+                truncate(&column.dtype().to_string(), config.max_column_width),
+            ];
+            cells.extend((0..shown).map(|row| {
+                let value = cell(column, row).unwrap_or_else(|| "null".to_string());
+                truncate(&value, config.max_column_width)
+            }));
+            (cells, is_numeric(column))
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|(cells, _)| {
+            cells
+                .iter()
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut lines = Vec::with_capacity(shown + 3);
+    for line in 0..shown + 2 {
+        let row: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .map(|((cells, numeric), &width)| {
+                // names and dtypes stay left aligned
+                if *numeric && line >= 2 {
+                    format!("{:>width$}", cells[line])
+                } else {
+                    format!("{:<width$}", cells[line])
+                }
+            })
+            .collect();
+        lines.push(row.join("  ").trim_end().to_string());
+        if line == 1 {
+            let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+            lines.push(rule.join("  "));
+        }
+    }
+    if df.height() > shown {
+        lines.push(format!(
+            "… {} more rows ({} total)",
+            df.height() - shown,
+            df.height()
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// A GitHub flavoured Markdown table, numbers right aligned and nulls left empty.
+pub fn markdown(df: &DataFrame) -> String {
+    let header: Vec<String> = df
+        .get_column_names()
+        .iter()
+        .map(|name| markdown_cell(name))
+        .collect();
+    let alignment: Vec<&str> = df
+        .get_columns()
+        .iter()
+        .map(|column| if is_numeric(column) { "---:" } else { "---" })
+        .collect();
+    let mut lines = vec![
+        format!("| {} |", header.join(" | ")),
+        format!("| {} |", alignment.join(" | ")),
+    ];
+    for row in 0..df.height() {
+        let cells: Vec<String> = df
+            .get_columns()
+            .iter()
+            .map(|column| cell(column, row).map_or_else(String::new, |value| markdown_cell(&value)))
+            .collect();
+        lines.push(format!("| {} |", cells.join(" | ")));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A standalone HTML page holding the frame as one table.
+pub fn html(df: &DataFrame) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>polars_cli output</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }\n\
+         th { background: #f3f3f3; }\n\
+         td.number { text-align: right; font-variant-numeric: tabular-nums; }\n\
+         </style>\n</head>\n<body>\n<table>\n<thead>\n<tr>",
+    );
+    for name in df.get_column_names() {
+        let _ = write!(page, "<th>{}</th>", escape_html(name));
+    }
+    page.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in 0..df.height() {
+        page.push_str("<tr>");
+        for column in df.get_columns() {
+            let value = cell(column, row).map_or_else(String::new, |value| escape_html(&value));
+            if is_numeric(column) {
+                let _ = write!(page, "<td class=\"number\">{value}</td>");
+            } else {
+                let _ = write!(page, "<td>{value}</td>");
+            }
+        }
+        page.push_str("</tr>\n");
+    }
+    page.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    page
+}
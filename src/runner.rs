@@ -1,16 +1,27 @@
 use crate::{
+    avro,
     config::{self, Config},
-    configs::input::InputFormat,
+    configs::{
+        self,
+        input::{InputConfig, InputFormat, STDIN_LOCATION},
+    },
+    inputs,
     outputs::OutputConnector,
+    parsers, sqlite,
+};
+use polars::{lazy::frame::pivot, prelude::*};
+use polars_io::{
+    cloud::CloudOptions,
+    ipc::{IpcStreamReader, IpcWriterOptions},
+    json::{JsonFormat, JsonReader},
 };
-use polars::prelude::*;
-use polars_io::{avro::AvroReader, cloud::CloudOptions};
 use std::{
-    collections::HashMap,
-    fs::{self, File},
-    io::{BufRead, BufReader},
+    fs::File,
+    io::{BufWriter, Cursor, Write},
+    path::Path,
+    sync::Arc,
 };
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Debug)]
 pub enum RunnerError {
@@ -44,8 +55,55 @@ impl From<String> for RunnerError {
     }
 }
 
-pub fn process_dataframe(mut df: LazyFrame, config: &Config) -> Result<LazyFrame, RunnerError> {
-    for operation in &config.operations {
+/// Apply the top level operations of `config` to a frame.
+///
+/// # Errors
+/// When an operation cannot be built or a source it joins with cannot be loaded.
+pub fn process_dataframe(df: LazyFrame, config: &Config) -> Result<LazyFrame, RunnerError> {
+    process_operations(df, &config.operations, config, &mut Vec::new())
+}
+
+/// Load a named entry from `pipelines` or `inputs`. Pipelines win when both use the same name.
+///
+/// # Errors
+/// When no source has the name, the pipelines depend on themselves or the input fails to load.
+pub fn dataframe_from_source(name: &str, config: &Config) -> Result<LazyFrame, RunnerError> {
+    source_dataframe(name, config, &mut Vec::new())
+}
+
+fn source_dataframe(
+    name: &str,
+    config: &Config,
+    resolving: &mut Vec<String>,
+) -> Result<LazyFrame, RunnerError> {
+    if resolving.iter().any(|source| source == name) {
+        return Err(RunnerError::Other(format!(
+            "Pipeline '{name}' depends on itself: {} -> {name}",
+            resolving.join(" -> ")
+        )));
+    }
+    resolving.push(name.to_string());
+    let df = if let Some(pipeline) = config.pipelines.get(name) {
+        let df = source_dataframe(&pipeline.input, config, resolving)?;
+        process_operations(df, &pipeline.operations, config, resolving)?
+    } else if let Some(input_config) = config.inputs.get(name) {
+        dataframe_from_input(input_config)?
+    } else {
+        return Err(RunnerError::Other(format!(
+            "No input or pipeline named '{name}' in the configuration."
+        )));
+    };
+    resolving.pop();
+    Ok(df)
+}
+
+fn process_operations(
+    mut df: LazyFrame,
+    operations: &[config::Operation],
+    config: &Config,
+    resolving: &mut Vec<String>,
+) -> Result<LazyFrame, RunnerError> {
+    for operation in operations {
         match operation {
             config::Operation::Filter { .. } => {
                 df = df.filter(operation.to_polars_expr().map_err(|e| {
@@ -84,6 +142,9 @@ pub fn process_dataframe(mut df: LazyFrame, config: &Config) -> Result<LazyFrame
                     df = df.limit(*limit as u32);
                 }
             }
+            config::Operation::Limit { rows } => {
+                df = df.limit(*rows);
+            }
             config::Operation::SelfJoin {
                 left_on,
                 right_on,
@@ -101,6 +162,37 @@ pub fn process_dataframe(mut df: LazyFrame, config: &Config) -> Result<LazyFrame
                 let df_join = df.clone().join(df, left, right, JoinArgs::new(how.into()));
                 df = df_join;
             }
+            config::Operation::Join {
+                right,
+                left_on,
+                right_on,
+                how,
+                suffix,
+            } => {
+                let right_df = source_dataframe(right, config, resolving)?;
+                let left = left_on.iter().map(|s| col(s.as_str())).collect::<Vec<_>>();
+                let right = right_on.iter().map(|s| col(s.as_str())).collect::<Vec<_>>();
+                let args = JoinArgs::new(how.into()).with_suffix(suffix.as_deref().map(Into::into));
+                df = df.join(right_df, left, right, args);
+            }
+            config::Operation::Concat { inputs, how } => {
+                let mut frames = vec![df];
+                for name in inputs {
+                    frames.push(source_dataframe(name, config, resolving)?);
+                }
+                // drifting schemas (e.g. int in one service, float in another) are cast to
+                // a common supertype instead of failing the union.
+                let args = UnionArgs {
+                    to_supertypes: true,
+                    ..Default::default()
+                };
+                df = match how {
+                    config::ConcatMode::Vertical => concat(frames, args),
+                    config::ConcatMode::Diagonal => concat_lf_diagonal(frames, args),
+                    config::ConcatMode::Horizontal => concat_lf_horizontal(frames, args),
+                }
+                .map_err(|e| RunnerError::Other(format!("Could not concat {operation:?} - {e}")))?;
+            }
             config::Operation::WithColumn { name, expression } => {
                 let mut expression = expression.to_polars_expr().map_err(|e| {
                     RunnerError::Other(format!(
@@ -158,7 +250,47 @@ pub fn process_dataframe(mut df: LazyFrame, config: &Config) -> Result<LazyFrame
                     .map_err(RunnerError::Polars)?
                     .lazy();
             }
-            config::Operation::Pivot { .. } => todo!(),
+            config::Operation::Pivot {
+                index,
+                columns,
+                values,
+                sort_columns,
+                aggregate_function,
+            } => {
+                // pivot needs every distinct value of `columns` to build the schema,
+                // so the frame has to be materialized first.
+                let materialized = df.collect()?;
+                let aggregate = aggregate_function
+                    .as_ref()
+                    .map(config::AllowedGroupFunction::to_pivot_expr);
+                df = pivot::pivot_stable(
+                    &materialized,
+                    columns,
+                    Some(index),
+                    Some(values),
+                    *sort_columns,
+                    aggregate,
+                    None,
+                )
+                .map_err(|e| RunnerError::Other(format!("Could not pivot {operation:?} - {e}")))?
+                .lazy();
+            }
+            config::Operation::Unpivot {
+                index,
+                on,
+                variable_name,
+                value_name,
+            } => {
+                let schema = df.collect_schema()?;
+                let index = config::ColumnSelector::resolve_all(index, &schema)?;
+                let on = config::ColumnSelector::resolve_all(on, &schema)?;
+                df = df.unpivot(UnpivotArgsDSL {
+                    on: on.into_iter().map(Selector::from).collect(),
+                    index: index.into_iter().map(Selector::from).collect(),
+                    variable_name: variable_name.as_deref().map(PlSmallStr::from_str),
+                    value_name: value_name.as_deref().map(PlSmallStr::from_str),
+                });
+            }
         }
     }
     Ok(df)
@@ -170,122 +302,471 @@ pub fn dataframe_from_file(config: &Config) -> Result<LazyFrame, RunnerError> {
             "No input file provided in the configuration.".to_string(),
         ));
     }
-    let input_config = config.input.as_ref().unwrap();
+    dataframe_from_input(config.input.as_ref().unwrap())
+}
+
+/// Load one input as a lazy frame.
+///
+/// # Errors
+/// When the input cannot be opened or read in its format.
+///
+/// # Panics
+/// When a Csv input has an empty delimiter.
+pub fn dataframe_from_input(input_config: &InputConfig) -> Result<LazyFrame, RunnerError> {
     info!("Processing input data from: {}", input_config.location);
 
-    fn load_local_path(input_path: &str) -> Result<File, RunnerError> {
-        std::fs::File::open(&input_path).map_err(|e| {
-            RunnerError::Io(format!(
-                "Error opening local input: 
-        '{input_path}' {e}"
-            ))
-        })
+    if input_config.is_stdin() && matches!(input_config.format, InputFormat::JsonLines { .. }) {
+        return stdin_json_lines(input_config);
     }
+    dataframe_from_files(input_config, input_files(input_config)?, None)
+}
+
+/// Read local, already decompressed files with the format options of `input_config`.
+/// `jsonl_schema` is used for JSON lines files instead of inferring one from their lines.
+///
+/// # Errors
+/// When a file cannot be read in the input's format.
+pub fn dataframe_from_files(
+    input_config: &InputConfig,
+    files: Vec<inputs::InputFile>,
+    jsonl_schema: Option<SchemaRef>,
+) -> Result<LazyFrame, RunnerError> {
+    let files = rewrite_csv_delimiter(input_config, files)?;
+    let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+    let include_file_paths = input_config
+        .include_file_paths
+        .as_deref()
+        .map(PlSmallStr::from_str);
+
+    let is_cloud = match &input_config.format {
+        InputFormat::Csv { is_cloud, .. } | InputFormat::JsonLines { is_cloud, .. } => *is_cloud,
+        _ => false,
+    } || inputs::is_cloud_location(&input_config.location);
+    let cloud_options = if is_cloud {
+        Some(
+            input_config
+                .cloud
+                .to_cloud_options(&input_config.location)?,
+        )
+    } else {
+        None
+    };
 
     let df = match &input_config.format {
-        InputFormat::Csv {
-            delimiter,
-            has_header,
-            ..
-        } => {
-            let delimiter = delimiter.as_bytes().first().expect("delimiter is empty");
-            LazyCsvReader::new(input_config.location.clone())
-                .with_separator(*delimiter)
-                .with_has_header(*has_header)
+        InputFormat::Csv { .. } => {
+            let reader = csv_reader(input_config, cloud_options)?;
+            scan_files(&files, include_file_paths, |sources, include_file_paths| {
+                reader
+                    .clone()
+                    .with_sources(sources)
+                    .with_include_file_paths(include_file_paths)
+                    .finish()
+            })?
+        }
+        InputFormat::JsonLines { skip_sample, .. } => {
+            // support skipping schema inference for jsonl, cloud objects are sampled by polars
+            let schema = match jsonl_schema {
+                Some(schema) => Some(schema),
+                None if *skip_sample || is_cloud => None,
+                None => Some(inputs::infer_jsonl_schema(&paths)?),
+            };
+
+            scan_files(&files, include_file_paths, |sources, include_file_paths| {
+                LazyJsonLineReader::new_with_sources(sources)
+                    .with_cloud_options(cloud_options.clone())
+                    .with_ignore_errors(true)
+                    .with_schema(schema.clone())
+                    .with_include_file_paths(include_file_paths)
+                    .finish()
+            })?
+        }
+        InputFormat::Json {
+            records_path,
+            schema,
+        } => read_json_documents(
+            &files,
+            records_path.as_deref(),
+            schema.as_ref(),
+            include_file_paths.as_ref(),
+        )?,
+        InputFormat::Parquet { .. } => {
+            scan_files(&files, include_file_paths, |sources, include_file_paths| {
+                let mut args = ScanArgsParquet {
+                    cloud_options: cloud_options.clone(),
+                    include_file_paths,
+                    ..Default::default()
+                };
+                args.hive_options.enabled = hive_partitioning(&sources);
+                LazyFrame::scan_parquet_sources(sources, args)
+            })?
+        }
+        // arrow data piped between processes uses the IPC stream format, not the file format
+        InputFormat::Ipc if input_config.is_stdin() => {
+            IpcStreamReader::new(load_local_path(&paths[0])?)
                 .finish()
                 .map_err(RunnerError::Polars)?
                 .lazy()
         }
-        InputFormat::JsonLines { skip_sample, .. } => {
-            let mut schema: Option<_> = None;
-            if !skip_sample {
-                let sample_file = std::fs::File::open(&input_config.location)
-                    .map_err(|e| format!("Error reading local file for schema extraction: {e}"))?;
-                let reader = BufReader::new(sample_file);
-                let sample_data: Vec<String> =
-                    reader.lines().take(10_000).filter_map(Result::ok).collect();
+        InputFormat::Ipc => {
+            scan_files(&files, include_file_paths, |sources, include_file_paths| {
+                let mut args = ScanArgsIpc {
+                    cloud_options: cloud_options.clone(),
+                    include_file_paths,
+                    ..Default::default()
+                };
+                args.hive_options.enabled = hive_partitioning(&sources);
+                LazyFrame::scan_ipc_sources(sources, args)
+            })?
+        }
+        InputFormat::Avro => read_avro_files(&files, include_file_paths.as_ref())?,
+        InputFormat::Sqlite { table, query } => read_sqlite_files(
+            &files,
+            table.as_deref(),
+            query.as_deref(),
+            include_file_paths.as_ref(),
+        )?,
+        InputFormat::NginxCombined
+        | InputFormat::ApacheCommon
+        | InputFormat::Logfmt
+        | InputFormat::Syslog
+        | InputFormat::Regex { .. } => {
+            parse_text_logs(input_config, &files, include_file_paths.as_ref())?
+        }
+    };
+    // temporary copies are removed here, the scans read them through open files
+    drop(files);
+    Ok(df)
+}
 
-                let pid = std::process::id();
-                let rand = rand::random::<u64>();
-                let schema_sample = format!("schema_sample_{pid}_{rand}.json");
-                std::fs::write(&schema_sample, sample_data.join("\n")).map_err(|e| {
-                    format!("Error writing file for schema extraction: {schema_sample} - {e}")
-                })?;
+/// Resolve the files an input reads, standard input is buffered into a temporary copy.
+fn input_files(input_config: &InputConfig) -> Result<Vec<inputs::InputFile>, RunnerError> {
+    if input_config.is_stdin() {
+        return Ok(vec![inputs::spool_stdin(input_config.compression)?]);
+    }
+    Ok(inputs::decompress_inputs(
+        inputs::resolve_paths(&input_config.location)?,
+        input_config.compression,
+    )?)
+}
 
-                let df_sample = JsonLineReader::new(File::open(&schema_sample).map_err(|e| {
-                    format!(
-                        "Error reading sample file for schema extraction: {schema_sample} - {e}"
-                    )
-                })?)
-                .infer_schema_len(Some(10_000.try_into().expect("always not 0")))
-                .finish()
-                .map_err(RunnerError::Polars)?;
-                fs::remove_file(&schema_sample).map_err(|e| {
-                    format!(
-                    "Error removing temporary file for schema extraction: {schema_sample} - {e}"
-                )
-                })?;
+/// Multi-byte csv delimiters are rewritten to a single byte polars can split on.
+fn rewrite_csv_delimiter(
+    input_config: &InputConfig,
+    files: Vec<inputs::InputFile>,
+) -> Result<Vec<inputs::InputFile>, RunnerError> {
+    match &input_config.format {
+        InputFormat::Csv {
+            delimiter,
+            quote_char,
+            comment_prefix,
+            ..
+        } => match inputs::csv_separator(delimiter)? {
+            inputs::CsvSeparator::Multi(delimiter) => Ok(inputs::rewrite_delimiter(
+                files,
+                &delimiter,
+                quote_char.bytes().next(),
+                comment_prefix.as_deref().map(str::as_bytes),
+            )?),
+            inputs::CsvSeparator::Byte(_) => Ok(files),
+        },
+        _ => Ok(files),
+    }
+}
 
-                schema = Some(df_sample.schema().clone());
-            }
-            // support skipping schema inference for jsonl
+fn stdin_json_lines(input_config: &InputConfig) -> Result<LazyFrame, RunnerError> {
+    let mut df = scan_ipc_copy(&inputs::stdin_json_lines(input_config.compression)?)?;
+    if let Some(column) = &input_config.include_file_paths {
+        df = df.with_column(lit(STDIN_LOCATION).alias(column.as_str()));
+    }
+    Ok(df)
+}
 
-            let mut reader =
-                LazyJsonLineReader::new(&input_config.location).with_ignore_errors(true);
-            if let Some(schema) = schema {
-                reader = reader.with_schema(Some(schema.clone()));
-            }
-            reader.finish().map_err(RunnerError::Polars)?.lazy()
+/// The lazy Csv reader for an input's options, without sources yet.
+fn csv_reader(
+    input_config: &InputConfig,
+    cloud_options: Option<CloudOptions>,
+) -> Result<LazyCsvReader, RunnerError> {
+    let InputFormat::Csv {
+        is_cloud: _,
+        delimiter,
+        has_header,
+        schema,
+        quote_char,
+        comment_prefix,
+        skip_rows,
+        null_values,
+        infer_schema_length,
+        try_parse_dates,
+        truncate_ragged_lines,
+        encoding,
+    } = &input_config.format
+    else {
+        return Err(RunnerError::Other("Not a Csv input".to_string()));
+    };
+    let separator = match inputs::csv_separator(delimiter)? {
+        inputs::CsvSeparator::Byte(separator) => separator,
+        inputs::CsvSeparator::Multi(_) => inputs::REWRITTEN_DELIMITER,
+    };
+    let mut reader = LazyCsvReader::new_with_sources(ScanSources::default())
+        .with_separator(separator)
+        .with_has_header(*has_header)
+        .with_quote_char(quote_char.bytes().next())
+        .with_comment_prefix(comment_prefix.as_deref().map(PlSmallStr::from_str))
+        .with_skip_rows(*skip_rows)
+        .with_try_parse_dates(*try_parse_dates)
+        .with_truncate_ragged_lines(*truncate_ragged_lines)
+        .with_encoding(encoding.into())
+        .with_cloud_options(cloud_options);
+    if let Some(length) = infer_schema_length {
+        reader = reader.with_infer_schema_length((*length > 0).then_some(*length));
+    }
+    if !null_values.is_empty() {
+        reader = reader.with_null_values(Some(NullValues::AllColumns(
+            null_values.iter().map(PlSmallStr::from).collect(),
+        )));
+    }
+    if let Some(schema) = schema {
+        reader = reader.with_dtype_overwrite(Some(Arc::new(schema.to_polars_schema())));
+    }
+    Ok(reader)
+}
+
+/// Read json documents one by one, each holding an array of records, into one frame.
+fn read_json_documents(
+    files: &[inputs::InputFile],
+    records_path: Option<&str>,
+    schema: Option<&configs::schema::Schema>,
+    include_file_paths: Option<&PlSmallStr>,
+) -> Result<LazyFrame, RunnerError> {
+    let schema_overwrite = schema.map(configs::schema::Schema::to_polars_schema);
+    let mut frames = Vec::with_capacity(files.len());
+    for file in files {
+        let path = &file.path;
+        let records = inputs::json_records(path, records_path)?;
+        let mut reader = JsonReader::new(Cursor::new(records))
+            .with_json_format(JsonFormat::Json)
+            .infer_schema_len(std::num::NonZeroUsize::new(inputs::SCHEMA_SAMPLE_LINES));
+        if let Some(schema_overwrite) = &schema_overwrite {
+            reader = reader.with_schema_overwrite(schema_overwrite);
         }
-        InputFormat::Json => LazyJsonLineReader::new(&input_config.location)
-            .finish()
-            .map_err(RunnerError::Polars)?
-            .lazy(),
-        InputFormat::Parquet { .. } => {
-            let mut args = ScanArgsParquet::default();
-            let cloud_options = CloudOptions::from_untyped_config(
-                &input_config.location,
-                HashMap::<String, String>::new(),
-            );
-            if let Ok(cloud_options) = cloud_options {
-                args.cloud_options = Some(cloud_options);
-            }
-            LazyFrame::scan_parquet(&input_config.location, args).map_err(RunnerError::Polars)?
+        let mut df = reader.finish().map_err(RunnerError::Polars)?.lazy();
+        if let Some(column) = include_file_paths {
+            df = df.with_column(lit(file.original.display().to_string()).alias(column.clone()));
         }
-        InputFormat::Ipc => {
-            let mut args = ScanArgsIpc::default();
-            let cloud_options = CloudOptions::from_untyped_config(
-                &input_config.location,
-                HashMap::<String, String>::new(),
-            );
-            if let Ok(cloud_options) = cloud_options {
-                args.cloud_options = Some(cloud_options);
-            }
-            LazyFrame::scan_ipc(&input_config.location, args).map_err(RunnerError::Polars)?
+        frames.push(df);
+    }
+    // documents from different api dumps rarely agree on every field
+    concat_lf_diagonal(
+        frames,
+        UnionArgs {
+            to_supertypes: true,
+            ..Default::default()
+        },
+    )
+    .map_err(RunnerError::Polars)
+}
+
+fn read_avro_files(
+    files: &[inputs::InputFile],
+    include_file_paths: Option<&PlSmallStr>,
+) -> Result<LazyFrame, RunnerError> {
+    let mut frames = Vec::with_capacity(files.len());
+    for file in files {
+        // copied block by block into IPC, which is scanned lazily like any other file
+        let spool = inputs::TempFile::new("avro_input.arrow");
+        avro::avro_to_ipc(&file.path, spool.path()).map_err(RunnerError::Polars)?;
+        let spool = inputs::InputFile::copy(file.path.clone(), spool);
+        let mut df = scan_ipc_copy(&spool)?;
+        if let Some(column) = include_file_paths {
+            df = df.with_column(lit(file.original.display().to_string()).alias(column.clone()));
         }
-        InputFormat::Avro => AvroReader::new(load_local_path(&input_config.location)?)
-            .finish()
-            .map_err(RunnerError::Polars)?
-            .lazy(),
+        frames.push(df);
+    }
+    concat(frames, UnionArgs::default()).map_err(RunnerError::Polars)
+}
+
+/// Read the same table or query from every `SQLite` file.
+fn read_sqlite_files(
+    files: &[inputs::InputFile],
+    table: Option<&str>,
+    query: Option<&str>,
+    include_file_paths: Option<&PlSmallStr>,
+) -> Result<LazyFrame, RunnerError> {
+    let mut frames = Vec::with_capacity(files.len());
+    for file in files {
+        let mut df = sqlite::read_sqlite(&file.path, table, query)?.lazy();
+        if let Some(column) = include_file_paths {
+            df = df.with_column(lit(file.original.display().to_string()).alias(column.clone()));
+        }
+        frames.push(df);
+    }
+    concat(frames, UnionArgs::default()).map_err(RunnerError::Polars)
+}
+
+/// Parse text log files line by line with the parser for the input's format.
+fn parse_text_logs(
+    input_config: &InputConfig,
+    files: &[inputs::InputFile],
+    include_file_paths: Option<&PlSmallStr>,
+) -> Result<LazyFrame, RunnerError> {
+    let parser =
+        parsers::line_parser(&input_config.format)?.expect("line based formats have a parser");
+    let mut rejects = match &input_config.format {
+        InputFormat::Regex {
+            reject_path: Some(reject_path),
+            ..
+        } => Some(BufWriter::new(File::create(reject_path)?)),
+        _ => None,
     };
-    Ok(df)
+    let mut frames = Vec::with_capacity(files.len());
+    let mut rejected = 0;
+    for file in files {
+        let parsed = parsers::parse_file(
+            parser.as_ref(),
+            &file.path,
+            rejects.as_mut().map(|rejects| rejects as &mut dyn Write),
+        )?;
+        rejected += parsed.rejected;
+        let mut df = parsers::typed_frame(parser.as_ref(), parsed.df)?;
+        if let Some(column) = include_file_paths {
+            df = df.with_column(lit(file.original.display().to_string()).alias(column.clone()));
+        }
+        frames.push(df);
+    }
+    if let Some(rejects) = rejects.as_mut() {
+        rejects.flush()?;
+    }
+    if rejected > 0 {
+        warn!(
+            "{rejected} lines of {} did not match the input format",
+            input_config.location
+        );
+    }
+    concat_lf_diagonal(
+        frames,
+        UnionArgs {
+            to_supertypes: true,
+            ..Default::default()
+        },
+    )
+    .map_err(RunnerError::Polars)
+}
+
+fn load_local_path(input_path: &Path) -> Result<File, RunnerError> {
+    std::fs::File::open(input_path).map_err(|e| {
+        RunnerError::Io(format!(
+            "Error opening local input: 
+        '{}' {e}",
+            input_path.display()
+        ))
+    })
+}
+
+/// Lazily scan local or cloud `files` with `scan`. Files are scanned together unless some
+/// are temporary copies, those are scanned one by one from an open file and get their
+/// `include_file_paths` value from the original location.
+fn scan_files(
+    files: &[inputs::InputFile],
+    include_file_paths: Option<PlSmallStr>,
+    scan: impl Fn(ScanSources, Option<PlSmallStr>) -> PolarsResult<LazyFrame>,
+) -> Result<LazyFrame, RunnerError> {
+    if !files.iter().any(inputs::InputFile::is_copy) {
+        let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+        return scan(ScanSources::Paths(paths.into()), include_file_paths)
+            .map_err(RunnerError::Polars);
+    }
+    let mut frames = Vec::with_capacity(files.len());
+    for file in files {
+        let mut df = scan(file.scan_source()?, None).map_err(RunnerError::Polars)?;
+        if let Some(column) = &include_file_paths {
+            df = df.with_column(lit(file.original.display().to_string()).alias(column.clone()));
+        }
+        frames.push(df);
+    }
+    concat(frames, UnionArgs::default()).map_err(RunnerError::Polars)
+}
+
+// an open file has no path to read hive partitions from, None leaves it to polars
+fn hive_partitioning(sources: &ScanSources) -> Option<bool> {
+    match sources {
+        ScanSources::Paths(_) => None,
+        _ => Some(false),
+    }
+}
+
+/// Scan an Arrow IPC file written by `polars_cli` itself, such as a spool.
+fn scan_ipc_copy(copy: &inputs::InputFile) -> Result<LazyFrame, RunnerError> {
+    let sources = copy.scan_source()?;
+    let mut args = ScanArgsIpc::default();
+    args.hive_options.enabled = hive_partitioning(&sources);
+    LazyFrame::scan_ipc_sources(sources, args).map_err(RunnerError::Polars)
 }
 
 pub fn run_with_output(config: Config) -> Result<(), RunnerError> {
-    let df = run(&config)?;
-    if let Some(output_configs) = config.outputs.as_ref() {
-        for output_config in output_configs.iter() {
-            let output: Box<dyn OutputConnector> = output_config
+    write_dataframe(run(&config)?, &config)
+}
+
+/// Send a processed frame to every configured output.
+///
+/// # Errors
+/// When an output cannot be created or written.
+pub fn write_dataframe(df: LazyFrame, config: &Config) -> Result<(), RunnerError> {
+    let Some(output_configs) = config.outputs.as_ref() else {
+        return Ok(());
+    };
+    // converted up front, so a bad output config fails before the pipeline runs
+    let outputs = output_configs
+        .iter()
+        .map(|output_config| {
+            output_config
                 .try_into()
-                .map_err(|e| RunnerError::Other(format!("Could not convert to output {e}")))?;
-            output
-                .stream(df.clone())
-                .map_err(|e| RunnerError::Other(format!("Could not write output - {e}")))?;
-        }
+                .map_err(|e| RunnerError::Other(format!("Could not convert to output {e}")))
+        })
+        .collect::<Result<Vec<Box<dyn OutputConnector>>, RunnerError>>()?;
+    let df = if outputs.len() > 1 {
+        spool_dataframe(df)?
+    } else {
+        df
+    };
+    for (output, output_config) in outputs.iter().zip(output_configs) {
+        let df = process_operations(
+            df.clone(),
+            output_config.operations(),
+            config,
+            &mut Vec::new(),
+        )?;
+        output
+            .stream(df)
+            .map_err(|e| RunnerError::Other(format!("Could not write output - {e}")))?;
     }
     Ok(())
 }
 
+/// Run the plan once into a temporary IPC file and scan it back, so several outputs share one
+/// pass over the input instead of each re-running the whole pipeline. The streaming sink keeps
+/// memory bounded however large the result is.
+fn spool_dataframe(df: LazyFrame) -> Result<LazyFrame, RunnerError> {
+    let spool = inputs::TempFile::new("outputs.arrow");
+    info!(
+        "Writing pipeline result once for all outputs to {}",
+        spool.path().display()
+    );
+    df.sink_ipc(
+        SinkTarget::Path(Arc::new(spool.path().to_path_buf())),
+        IpcWriterOptions::default(),
+        None,
+        SinkOptions {
+            maintain_order: true,
+            ..Default::default()
+        },
+    )
+    .and_then(LazyFrame::collect)
+    .map_err(RunnerError::Polars)?;
+    // read through an open file, the spool is gone from disk once this returns
+    let spool = inputs::InputFile::copy(spool.path().to_path_buf(), spool);
+    scan_ipc_copy(&spool)
+}
+
 pub fn run(config: &Config) -> Result<LazyFrame, RunnerError> {
     let df = dataframe_from_file(&config)?;
     let df = process_dataframe(df, &config)?;
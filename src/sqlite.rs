@@ -0,0 +1,236 @@
+use crate::{
+    configs::output::{DatabaseOutputConfig, DatabaseWriteMode},
+    database,
+    outputs::OutputError,
+};
+use polars::prelude::*;
+use rusqlite::{params_from_iter, types::Value, Connection, OpenFlags};
+use std::{fs, path::Path};
+
+// bound parameters a single statement may have in sqlite 3.32 and later
+const SQLITE_MAX_VARIABLES: usize = 32_766;
+
+/// Dtype for a declared column type, following `SQLite`'s type affinity rules. None when the
+/// column has no declared type, e.g. an expression in a query.
+fn declared_dtype(decl_type: &str) -> Option<DataType> {
+    let decl_type = decl_type.to_ascii_uppercase();
+    let has = |affinity: &str| decl_type.contains(affinity);
+    if decl_type.is_empty() {
+        None
+    } else if has("INT") {
+        Some(DataType::Int64)
+    } else if has("CHAR") || has("CLOB") || has("TEXT") {
+        Some(DataType::String)
+    } else if has("BLOB") {
+        Some(DataType::Binary)
+    } else if has("REAL") || has("FLOA") || has("DOUB") {
+        Some(DataType::Float64)
+    } else if has("BOOL") {
+        Some(DataType::Boolean)
+    } else if has("DATE") || has("TIME") {
+        // sqlite has no date type, these are almost always ISO 8601 text
+        Some(DataType::String)
+    } else {
+        // NUMERIC and DECIMAL affinity
+        Some(DataType::Float64)
+    }
+}
+
+/// Dtype for a column without a declared type. Integers mixed with reals become floats,
+/// any other mix falls back to strings.
+fn value_dtype(values: &[AnyValue]) -> DataType {
+    let mut dtype = None;
+    for value in values {
+        let value_dtype = match value {
+            AnyValue::Null => continue,
+            value => value.dtype(),
+        };
+        dtype = match (dtype, value_dtype) {
+            (None, value_dtype) => Some(value_dtype),
+            (Some(dtype), value_dtype) if dtype == value_dtype => Some(dtype),
+            (Some(DataType::Int64 | DataType::Float64), DataType::Int64 | DataType::Float64) => {
+                Some(DataType::Float64)
+            }
+            _ => return DataType::String,
+        };
+    }
+    dtype.unwrap_or(DataType::String)
+}
+
+fn any_value(value: Value) -> AnyValue<'static> {
+    match value {
+        Value::Null => AnyValue::Null,
+        Value::Integer(value) => AnyValue::Int64(value),
+        Value::Real(value) => AnyValue::Float64(value),
+        Value::Text(value) => AnyValue::StringOwned(value.into()),
+        Value::Blob(value) => AnyValue::BinaryOwned(value),
+    }
+}
+
+/// Read a table, or the rows of a query, from a `SQLite` database file.
+///
+/// # Errors
+/// When the file cannot be opened, the query fails or neither or both of `table` and
+/// `query` are given.
+pub fn read_sqlite(
+    path: &Path,
+    table: Option<&str>,
+    query: Option<&str>,
+) -> Result<DataFrame, String> {
+    let query = match (table, query) {
+        (Some(table), None) => format!("SELECT * FROM \"{}\"", table.replace('"', "\"\"")),
+        (None, Some(query)) => query.to_string(),
+        _ => return Err("Sqlite input needs exactly one of `table` or `query`".to_string()),
+    };
+    let sqlite_error =
+        |e: rusqlite::Error| format!("Error reading sqlite {} - {e}", path.display());
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(sqlite_error)?;
+    let mut statement = connection.prepare(&query).map_err(sqlite_error)?;
+    let columns: Vec<(String, Option<DataType>)> = statement
+        .columns()
+        .iter()
+        .map(|column| {
+            (
+                column.name().to_string(),
+                column.decl_type().and_then(declared_dtype),
+            )
+        })
+        .collect();
+
+    let mut values: Vec<Vec<AnyValue<'static>>> = vec![Vec::new(); columns.len()];
+    let mut rows = statement.query([]).map_err(sqlite_error)?;
+    while let Some(row) = rows.next().map_err(sqlite_error)? {
+        for (index, column) in values.iter_mut().enumerate() {
+            column.push(any_value(row.get(index).map_err(sqlite_error)?));
+        }
+    }
+
+    let columns = columns
+        .into_iter()
+        .zip(values)
+        .map(|((name, dtype), values)| {
+            let dtype = dtype.unwrap_or_else(|| value_dtype(&values));
+            // sqlite columns can hold any type, values that do not fit the dtype become null
+            Series::from_any_values_and_dtype(name.into(), &values, &dtype, false).map(Column::from)
+        })
+        .collect::<PolarsResult<Vec<_>>>()
+        .map_err(|e| format!("Error building frame from sqlite {} - {e}", path.display()))?;
+    DataFrame::new(columns).map_err(|e| format!("Error building frame from sqlite - {e}"))
+}
+
+fn sqlite_type(dtype: &DataType) -> &'static str {
+    match dtype {
+        // declared so reading the table back gives booleans again
+        DataType::Boolean => "BOOLEAN",
+        DataType::Date | DataType::Datetime(..) => "DATETIME",
+        DataType::Binary => "BLOB",
+        dtype if dtype.is_integer() => "INTEGER",
+        dtype if dtype.is_float() => "REAL",
+        _ => "TEXT",
+    }
+}
+
+fn sqlite_value(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(value) => Value::Integer(value.into()),
+        AnyValue::Int8(value) => Value::Integer(value.into()),
+        AnyValue::Int16(value) => Value::Integer(value.into()),
+        AnyValue::Int32(value) => Value::Integer(value.into()),
+        AnyValue::Int64(value) => Value::Integer(value),
+        AnyValue::UInt8(value) => Value::Integer(value.into()),
+        AnyValue::UInt16(value) => Value::Integer(value.into()),
+        AnyValue::UInt32(value) => Value::Integer(value.into()),
+        AnyValue::UInt64(value) => {
+            i64::try_from(value).map_or_else(|_| Value::Text(value.to_string()), Value::Integer)
+        }
+        AnyValue::Float32(value) => Value::Real(value.into()),
+        AnyValue::Float64(value) => Value::Real(value),
+        AnyValue::String(value) => Value::Text(value.to_string()),
+        AnyValue::StringOwned(value) => Value::Text(value.to_string()),
+        AnyValue::Binary(value) => Value::Blob(value.to_vec()),
+        AnyValue::BinaryOwned(value) => Value::Blob(value),
+        // dates, datetimes and nested values are stored as their text form
+        value => Value::Text(value.to_string()),
+    }
+}
+
+/// Write a frame to a table of the `SQLite` database at `path`, creating both if needed.
+///
+/// # Errors
+/// When the database cannot be opened or written.
+pub fn write_sqlite(
+    path: &Path,
+    df: &DataFrame,
+    config: &DatabaseOutputConfig,
+) -> Result<(), OutputError> {
+    let sqlite_error = |e: rusqlite::Error| {
+        OutputError::Io(format!("Error writing sqlite {} - {e}", path.display()))
+    };
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let columns: Vec<(String, &str)> = df
+        .get_columns()
+        .iter()
+        .map(|column| (column.name().to_string(), sqlite_type(column.dtype())))
+        .collect();
+    let table = database::quote_identifier(&config.table);
+    let column_list = database::column_list(df);
+    let conflict = database::conflict_clause(df, config);
+
+    let mut connection = Connection::open(path).map_err(sqlite_error)?;
+    // everything, including a replace, is applied in one transaction
+    let transaction = connection.transaction().map_err(sqlite_error)?;
+    if config.mode == DatabaseWriteMode::Replace {
+        transaction
+            .execute_batch(&format!("DROP TABLE IF EXISTS {table}"))
+            .map_err(sqlite_error)?;
+    }
+    transaction
+        .execute_batch(&database::create_table_sql(&config.table, &columns))
+        .map_err(sqlite_error)?;
+    if config.mode == DatabaseWriteMode::Upsert {
+        transaction
+            .execute_batch(&database::upsert_index_sql(
+                &config.table,
+                &config.key_columns,
+            ))
+            .map_err(sqlite_error)?;
+    }
+
+    let row_placeholder = format!("({})", vec!["?"; columns.len()].join(", "));
+    let rows_per_insert = config
+        .batch_size
+        .min(SQLITE_MAX_VARIABLES / columns.len().max(1))
+        .max(1);
+    for offset in (0..df.height()).step_by(rows_per_insert) {
+        let rows = rows_per_insert.min(df.height() - offset);
+        let mut values = Vec::with_capacity(rows * columns.len());
+        for row in offset..offset + rows {
+            for column in df.get_columns() {
+                let value = column
+                    .get(row)
+                    .map_err(|e| OutputError::Other(format!("Failed to read row {row} - {e}")))?;
+                values.push(sqlite_value(value));
+            }
+        }
+        let insert = format!(
+            "INSERT INTO {table} ({column_list}) VALUES {}{conflict}",
+            vec![row_placeholder.as_str(); rows].join(", ")
+        );
+        // only the last batch has a different size, the statement is prepared once per size
+        transaction
+            .prepare_cached(&insert)
+            .and_then(|mut statement| statement.execute(params_from_iter(values)))
+            .map_err(sqlite_error)?;
+    }
+    transaction.commit().map_err(sqlite_error)
+}
@@ -0,0 +1,142 @@
+use polars_cli::follow::{check_batch_outputs, Batches, TailFile, MAX_BATCH_BYTES};
+use std::{fs, io::Write, path::PathBuf};
+
+mod test_utils;
+
+fn followed_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("polars_cli_follow_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn append(path: &PathBuf, contents: &str) {
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+}
+
+#[test]
+fn tail_reads_appended_lines_and_survives_rotation() {
+    let path = followed_file("deploys.csv", "service_name,version\napi-gateway,1.14.2\n");
+    let mut tail = TailFile::open(&path, false, true).unwrap();
+    assert!(tail.read_lines().unwrap().is_empty());
+
+    // the unfinished line waits for its newline
+    append(&path, "auth-service,3.2.0\ncheckout,");
+    assert_eq!(tail.read_lines().unwrap(), b"auth-service,3.2.0\n");
+    append(&path, "2.0.1\n");
+    assert_eq!(tail.read_lines().unwrap(), b"checkout,2.0.1\n");
+
+    fs::rename(&path, path.with_extension("csv.1")).unwrap();
+    fs::write(&path, "service_name,version\nsearch-service,0.9.0\n").unwrap();
+    assert_eq!(tail.read_lines().unwrap(), b"search-service,0.9.0\n");
+    assert_eq!(tail.header(), Some(&b"service_name,version\n"[..]));
+
+    // copytruncate keeps the inode but starts the file over
+    fs::write(&path, "service_name,version\n").unwrap();
+    assert!(tail.read_lines().unwrap().is_empty());
+    append(&path, "billing,4.1.0\n");
+    assert_eq!(tail.read_lines().unwrap(), b"billing,4.1.0\n");
+}
+
+#[test]
+fn batches_run_through_the_operations() {
+    let path = followed_file(
+        "checkout.jsonl",
+        "{\"endpoint\": \"/cart\", \"status_code\": 200}\n",
+    );
+    let config = test_utils::parse_config_str(&format!(
+        r#"
+[input]
+type = "JsonLines"
+location = "{}"
+
+[[operations]]
+type = "Filter"
+column = "status_code"
+condition = "GTE"
+filter = 500
+"#,
+        path.display()
+    ));
+    let mut tail = TailFile::open(&path, true, false).unwrap();
+    append(
+        &path,
+        "{\"endpoint\": \"/pay\", \"status_code\": 502}\n{\"endpoint\": \"/cart\", \"status_code\": 503}\n",
+    );
+    let lines = tail.read_lines().unwrap();
+    let mut batches = Batches::new(&config).unwrap();
+    let df = batches
+        .dataframe(tail.header(), &lines)
+        .unwrap()
+        .collect()
+        .unwrap();
+    assert_eq!(df.height(), 2);
+
+    // the schema of the first batch is kept, a field that shows up later is left out
+    append(
+        &path,
+        "{\"endpoint\": \"/pay\", \"status_code\": 500, \"region\": \"eu\"}\n",
+    );
+    let lines = tail.read_lines().unwrap();
+    let df = batches
+        .dataframe(tail.header(), &lines)
+        .unwrap()
+        .collect()
+        .unwrap();
+    assert_eq!(df.height(), 1);
+    assert_eq!(df.get_column_names(), ["endpoint", "status_code"]);
+}
+
+#[test]
+fn tail_reads_a_backlog_in_capped_batches() {
+    let line = format!("{}\n", "x".repeat(1023));
+    let lines = usize::try_from(MAX_BATCH_BYTES).unwrap() / line.len() + 10;
+    let path = followed_file("backlog.log", &line.repeat(lines));
+    let mut tail = TailFile::open(&path, true, false).unwrap();
+
+    let first = tail.read_lines().unwrap();
+    assert_eq!(first.len(), usize::try_from(MAX_BATCH_BYTES).unwrap());
+    assert!(tail.is_behind());
+    let rest = tail.read_lines().unwrap();
+    assert_eq!(rest.len(), 10 * line.len());
+    assert!(!tail.is_behind());
+}
+
+#[test]
+fn batch_outputs_must_not_overwrite() {
+    let outputs = |output: &str| {
+        test_utils::parse_config_str(&format!(
+            r#"
+[input]
+type = "JsonLines"
+location = "checkout.jsonl"
+
+[[outputs]]
+{output}
+"#
+        ))
+    };
+    let err = check_batch_outputs(&outputs(
+        r#"type = "File"
+format = "Jsonl"
+path = "errors.jsonl""#,
+    ))
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("errors.jsonl needs mode = \"Append\""));
+    assert!(check_batch_outputs(&outputs(
+        r#"type = "File"
+format = "Jsonl"
+path = "errors.jsonl"
+mode = "Append""#,
+    ))
+    .is_ok());
+    assert!(check_batch_outputs(&outputs(
+        r#"type = "Stdout"
+format = "Jsonl""#
+    ))
+    .is_ok());
+}
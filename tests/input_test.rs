@@ -0,0 +1,417 @@
+use polars::prelude::*;
+use polars_cli::runner::run;
+
+mod test_utils;
+
+fn collect(config: &str) -> DataFrame {
+    let config = test_utils::parse_config_str(config);
+    run(&config)
+        .and_then(|df| df.collect().map_err(polars_cli::runner::RunnerError::from))
+        .expect("pipeline should run")
+}
+
+#[test]
+fn glob_input_with_file_paths() {
+    let df = collect(
+        r#"
+[input]
+type = "JsonLines"
+location = "test_files/logs/2024-04-*/*.jsonl"
+include_file_paths = "source_file"
+
+[[operations]]
+type = "Select"
+columns = ["endpoint", "error_type", "source_file"]
+"#,
+    );
+    assert_eq!(df.height(), 3);
+    let sources = df.column("source_file").unwrap().str().unwrap();
+    assert!(sources
+        .into_iter()
+        .flatten()
+        .any(|path| path.ends_with("2024-04-02/api-gateway.jsonl")));
+    // error_type only exists in the second file and must survive schema sampling
+    assert_eq!(df.column("error_type").unwrap().null_count(), 2);
+}
+
+#[test]
+fn directory_input() {
+    let df = collect(
+        r#"
+[input]
+type = "JsonLines"
+location = "test_files/logs"
+
+[[operations]]
+type = "Select"
+columns = ["endpoint"]
+"#,
+    );
+    assert_eq!(df.height(), 3);
+}
+
+#[test]
+fn gzip_jsonl_input() {
+    let df = collect(
+        r#"
+[input]
+type = "JsonLines"
+location = "test_files/compressed/checkout_logs.jsonl.gz"
+include_file_paths = "source_file"
+"#,
+    );
+    assert_eq!(df.height(), 3);
+    let source = df.column("source_file").unwrap().str().unwrap().get(0);
+    assert_eq!(source, Some("test_files/compressed/checkout_logs.jsonl.gz"));
+}
+
+#[test]
+fn decompressed_copies_are_removed_once_the_plan_is_built() {
+    // a name of its own, other tests decompress the shared fixtures concurrently
+    let input = std::env::temp_dir().join("polars_cli_cleanup_owners.csv.zst");
+    std::fs::copy("test_files/compressed/service_owners.csv.zst", &input).unwrap();
+    let config = test_utils::parse_config_str(&format!(
+        "[input]\ntype = \"Csv\"\nlocation = \"{}\"",
+        input.display()
+    ));
+    let copies = || {
+        let pattern = std::env::temp_dir().join(format!(
+            "polars_cli_{}_*_polars_cli_cleanup_owners.csv",
+            std::process::id()
+        ));
+        glob::glob(pattern.to_str().unwrap()).unwrap().count()
+    };
+    let df = run(&config).unwrap();
+    assert_eq!(copies(), 0);
+    // the scan still reads the copy through its open file
+    assert_eq!(df.collect().unwrap().height(), 6);
+}
+
+#[test]
+fn compression_detected_from_magic_bytes() {
+    let df = collect(
+        r#"
+[input]
+type = "JsonLines"
+location = "test_files/compressed/checkout_logs_no_extension"
+"#,
+    );
+    assert_eq!(df.height(), 3);
+}
+
+#[test]
+fn zstd_and_bzip2_csv_inputs() {
+    let df = collect(
+        r#"
+[input]
+type = "Csv"
+location = "test_files/compressed/service_owners.csv.zst"
+
+[inputs.deploys]
+type = "Csv"
+location = "test_files/compressed/deploys.csv.bz2"
+compression = "Bzip2"
+
+[[operations]]
+type = "Join"
+right = "deploys"
+left_on = ["service_name"]
+right_on = ["service_name"]
+how = "Inner"
+"#,
+    );
+    assert_eq!(df.height(), 3);
+}
+
+#[test]
+fn stdin_input() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_polars_cli"))
+        .args(["--config", "test_files/stdin_errors.toml"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("polars_cli should start");
+    let logs = std::fs::read("test_files/compressed/checkout_logs.jsonl.gz").unwrap();
+    child.stdin.take().unwrap().write_all(&logs).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.contains("c7e2a9f0-8d41-4b6e-b0a2-6c1e9d4f7b22"));
+}
+
+#[test]
+fn stdin_json_lines_are_read_in_batches() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // more lines than the schema sample and one parse batch
+    let rows = polars_cli::inputs::STDIN_BATCH_LINES + 20_000;
+    let mut lines = String::new();
+    for i in 0..rows {
+        lines.push_str(&format!(
+            "{{\"request_id\": \"r{i}\", \"status_code\": 503, \"error_type\": null}}\n"
+        ));
+    }
+    let mut child = Command::new(env!("CARGO_BIN_EXE_polars_cli"))
+        .args(["--config", "test_files/stdin_errors.toml"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("polars_cli should start");
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(lines.as_bytes()));
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), rows);
+    assert!(stdout
+        .lines()
+        .last()
+        .unwrap()
+        .contains(&format!("r{}", rows - 1)));
+}
+
+#[test]
+fn csv_reader_options() {
+    let df = collect(
+        r##"
+[input]
+type = "Csv"
+location = "test_files/deploys_pipe.csv"
+delimiter = "||"
+comment_prefix = "#"
+null_values = ["N/A"]
+try_parse_dates = true
+schema = { columns = [ { name = "replicas", dtype = { type = "Int32" } } ] }
+"##,
+    );
+    assert_eq!(df.height(), 3);
+    assert_eq!(df.column("replicas").unwrap().dtype(), &DataType::Int32);
+    assert_eq!(df.column("replicas").unwrap().null_count(), 1);
+    assert_eq!(df.column("deployed_at").unwrap().dtype(), &DataType::Date);
+    let versions = df.column("version").unwrap().str().unwrap();
+    assert_eq!(versions.get(1), Some("3.2||rc1"));
+}
+
+#[test]
+fn json_array_input() {
+    let df = collect(
+        r#"
+[input]
+type = "Json"
+location = "test_files/requests_array.json"
+"#,
+    );
+    assert_eq!(df.height(), 2);
+    assert_eq!(df.column("status_code").unwrap().dtype(), &DataType::Int64);
+}
+
+#[test]
+fn json_records_path_with_schema_override() {
+    let df = collect(
+        r#"
+[input]
+type = "Json"
+location = "test_files/api_dump.json"
+records_path = "data.items"
+schema = { columns = [ { name = "tier", dtype = { type = "UInt8" } } ] }
+"#,
+    );
+    assert_eq!(df.height(), 3);
+    assert_eq!(df.column("tier").unwrap().dtype(), &DataType::UInt8);
+    assert_eq!(df.column("deprecated").unwrap().null_count(), 2);
+}
+
+#[test]
+fn nginx_combined_input() {
+    let df = collect(
+        r#"
+[input]
+type = "NginxCombined"
+location = "test_files/access_logs/nginx_access.log"
+"#,
+    );
+    // the free text line is skipped
+    assert_eq!(df.height(), 3);
+    assert_eq!(df.column("status_code").unwrap().dtype(), &DataType::Int64);
+    let errors = df.column("is_error").unwrap().bool().unwrap();
+    assert_eq!(errors.sum(), Some(2));
+    let latency = df.column("response_time_ms").unwrap().i64().unwrap();
+    assert_eq!(latency.get(1), Some(1250));
+    assert_eq!(latency.get(2), None);
+    let timestamps = df.column("timestamp").unwrap().str().unwrap();
+    assert_eq!(timestamps.get(0), Some("2024-04-01T10:00:01+00:00"));
+    assert_eq!(df.column("user_id").unwrap().null_count(), 2);
+}
+
+#[test]
+fn apache_common_input_with_readme_pipeline() {
+    let df = collect(
+        r#"
+[input]
+type = "ApacheCommon"
+location = "test_files/access_logs/apache_access.log"
+
+[[operations]]
+type = "GroupBy"
+columns = ["is_error"]
+aggregate = [
+  { column = "status_code", function = "COUNT", alias = "requests" },
+  { column = "request_id", function = "COUNT", alias = "request_ids" },
+]
+"#,
+    );
+    assert_eq!(df.height(), 2);
+    assert!(df.column("user_agent").is_err());
+    let requests = df
+        .column("requests")
+        .unwrap()
+        .cast(&DataType::Int64)
+        .unwrap();
+    assert_eq!(requests.i64().unwrap().sum(), Some(2));
+    // request_id is null for access logs
+    let request_ids = df
+        .column("request_ids")
+        .unwrap()
+        .cast(&DataType::Int64)
+        .unwrap();
+    assert_eq!(request_ids.i64().unwrap().sum(), Some(0));
+}
+
+#[test]
+fn logfmt_input() {
+    let df = collect(
+        r#"
+[input]
+type = "Logfmt"
+location = "test_files/access_logs/service.logfmt"
+"#,
+    );
+    assert_eq!(df.height(), 3);
+    assert_eq!(df.column("status_code").unwrap().dtype(), &DataType::Int64);
+    assert_eq!(
+        df.column("response_time_ms").unwrap().dtype(),
+        &DataType::Float64
+    );
+    let endpoints = df.column("endpoint").unwrap().str().unwrap();
+    assert_eq!(endpoints.get(1), Some("/api/v1/pay"));
+    let messages = df.column("msg").unwrap().str().unwrap();
+    assert_eq!(messages.get(1), Some("upstream \"payments\" timed out"));
+    assert_eq!(df.column("cached").unwrap().null_count(), 2);
+    let errors = df.column("is_error").unwrap().bool().unwrap();
+    assert_eq!(errors.get(1), Some(true));
+    assert_eq!(errors.sum(), Some(1));
+    assert_eq!(
+        df.column("service_name").unwrap().str().unwrap().get(0),
+        Some("checkout")
+    );
+}
+
+#[test]
+fn regex_input_with_reject_file() {
+    let reject_path = std::env::temp_dir().join("polars_cli_regex_rejects.log");
+    let df = collect(&format!(
+        r#"
+[input]
+type = "Regex"
+location = "test_files/access_logs/billing_worker.log"
+pattern = '^(?P<timestamp>\S+ \S+) \[(?P<worker>[^\]]+)\] (?P<level>\w+)\s+invoice=(?P<invoice>\S+) took=(?P<response_time_ms>\d+)ms$'
+dtypes = {{ response_time_ms = {{ type = "Int64" }} }}
+reject_path = "{}"
+"#,
+        reject_path.display()
+    ));
+    assert_eq!(df.height(), 3);
+    assert_eq!(
+        df.column("response_time_ms").unwrap().dtype(),
+        &DataType::Int64
+    );
+    assert_eq!(
+        df.column("level").unwrap().str().unwrap().get(1),
+        Some("ERROR")
+    );
+    let rejects = std::fs::read_to_string(&reject_path).unwrap();
+    assert_eq!(rejects, "  at billing::charge (charge.rs:42)\n");
+    std::fs::remove_file(reject_path).unwrap();
+}
+
+#[test]
+fn sqlite_table_input() {
+    let df = collect(
+        r#"
+[input]
+type = "Sqlite"
+location = "test_files/reference.db"
+table = "customers"
+"#,
+    );
+    assert_eq!(df.height(), 3);
+    let schema = df.schema();
+    assert_eq!(schema.get("id"), Some(&DataType::Int64));
+    assert_eq!(schema.get("tier"), Some(&DataType::String));
+    assert_eq!(schema.get("credit"), Some(&DataType::Float64));
+    assert_eq!(schema.get("active"), Some(&DataType::Boolean));
+    assert_eq!(schema.get("signed_up"), Some(&DataType::String));
+    assert_eq!(schema.get("avatar"), Some(&DataType::Binary));
+    let credit = df.column("credit").unwrap().f64().unwrap();
+    assert_eq!(credit.get(2), Some(80.0));
+}
+
+#[test]
+fn sqlite_query_input() {
+    let df = collect(
+        r#"
+[input]
+type = "Sqlite"
+location = "test_files/reference.db"
+query = "SELECT service_name, enabled = 1 AS enabled, length(flag) * 1.5 AS weight FROM feature_flags WHERE service_name != 'web'"
+"#,
+    );
+    assert_eq!(df.height(), 2);
+    assert_eq!(df.column("enabled").unwrap().dtype(), &DataType::Int64);
+    assert_eq!(df.column("weight").unwrap().dtype(), &DataType::Float64);
+}
+
+#[test]
+fn cloud_input_options_are_checked_before_scanning() {
+    let config = test_utils::parse_config_str(
+        r#"
+[input]
+type = "Parquet"
+location = "s3://polars-cli-test/owners/service_owners.parquet"
+
+[input.cloud]
+profile = "polars-cli-missing-profile"
+max_retries = 1
+timeout_secs = 5
+"#,
+    );
+    let error = run(&config).map(|_| ()).unwrap_err();
+    assert!(error.to_string().contains("polars-cli-missing-profile"));
+}
+
+#[test]
+fn cloud_json_lines_are_not_sampled_locally() {
+    let config = test_utils::parse_config_str(
+        r#"
+[input]
+type = "JsonLines"
+location = "s3://polars-cli-test/logs/app.jsonl"
+
+[input.cloud]
+endpoint_url = "http://127.0.0.1:9"
+max_retries = 1
+timeout_secs = 5
+"#,
+    );
+    let error = run(&config).and_then(|df| df.collect().map_err(Into::into));
+    let error = error.map(|_| ()).unwrap_err().to_string();
+    assert!(!error.contains("local file"), "{error}");
+}
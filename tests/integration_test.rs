@@ -1,3 +1,5 @@
+use polars::prelude::*;
+
 mod test_utils;
 
 config_string_test!(
@@ -206,3 +208,242 @@ aggregate = [
 
 "#
 );
+
+#[test]
+fn pivot_multiple_values() {
+    let config = test_utils::parse_config_str(
+        r#"
+[input]
+type = "Csv"
+location = "test_files/pivot_requests.csv"
+
+[[operations]]
+type = "Pivot"
+index = ["service_name"]
+columns = ["method"]
+values = ["response_time_ms", "request_size_bytes"]
+sort_columns = true
+aggregate_function = "SUM"
+"#,
+    );
+    let df = polars_cli::runner::run(&config)
+        .unwrap()
+        .collect()
+        .unwrap()
+        .sort(["service_name"], Default::default())
+        .unwrap();
+
+    // one column per value and method, the methods sorted within each value
+    let names: Vec<&str> = df
+        .get_column_names()
+        .into_iter()
+        .map(|name| name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "service_name",
+            "response_time_ms_GET",
+            "response_time_ms_POST",
+            "request_size_bytes_GET",
+            "request_size_bytes_POST",
+        ]
+    );
+    let column = |name: &str| df.column(name).unwrap().i64().unwrap().to_vec();
+    assert_eq!(column("response_time_ms_GET"), [Some(5), Some(30)]);
+    assert_eq!(column("response_time_ms_POST"), [None, Some(30)]);
+    assert_eq!(column("request_size_bytes_GET"), [Some(50), Some(300)]);
+}
+
+#[test]
+fn unpivot_metrics_after_group_by_time() {
+    let df = test_utils::collect_logs(
+        r#"
+[[operations]]
+type = "GroupByTime"
+time_column = "timestamp"
+every = 5
+unit = "Minutes"
+timestamp_format = "%Y-%m-%dT%H:%M:%S%z"
+output_column = "bucket"
+additional_groups = ["service_name"]
+aggregate = [
+  { column = "cpu_utilization", function = "MEAN" },
+  { column = "memory_utilization", function = "MEAN" },
+  { column = "disk_io", function = "MEAN" },
+  { column = "network_io", function = "MEAN" }
+]
+
+[[operations]]
+type = "Unpivot"
+index = ["bucket", "service_name"]
+on = ["^.*_utilization$", { dtype = { type = "Float64" } }]
+variable_name = "metric"
+value_name = "value"
+"#,
+    );
+    assert_eq!(
+        df.get_column_names(),
+        ["bucket", "service_name", "metric", "value"]
+    );
+    assert_eq!(df.column("value").unwrap().dtype(), &DataType::Float64);
+    // the regex picks the two utilization columns, the dtype selector adds the io means
+    let metrics = df.column("metric").unwrap().unique_stable().unwrap();
+    let mut metrics: Vec<&str> = metrics.str().unwrap().into_no_null_iter().collect();
+    metrics.sort_unstable();
+    assert_eq!(
+        metrics,
+        [
+            "cpu_utilization",
+            "disk_io",
+            "memory_utilization",
+            "network_io"
+        ]
+    );
+    let groups = df
+        .select(["bucket", "service_name"])
+        .unwrap()
+        .unique_stable(None, UniqueKeepStrategy::First, None)
+        .unwrap();
+    assert_eq!(df.height(), groups.height() * 4);
+}
+
+#[test]
+fn unpivot_selector_matching_nothing_fails() {
+    // an empty `on` would unpivot every other column instead
+    for on in [r#""^.*_latency$""#, r#"{ dtype = { type = "Boolean" } }"#] {
+        let config = test_utils::parse_config_str(&format!(
+            r#"
+[input]
+type = "Csv"
+location = "test_files/pivot_requests.csv"
+
+[[operations]]
+type = "Unpivot"
+index = ["service_name"]
+on = [{on}]
+"#
+        ));
+        let Err(error) = polars_cli::runner::run(&config) else {
+            panic!("unpivot on {on} should fail");
+        };
+        assert!(error.to_string().contains("matches no columns"), "{error}");
+    }
+}
+
+#[test]
+fn join_named_inputs() {
+    let df = test_utils::collect_logs(
+        r#"
+[inputs.owners]
+type = "Csv"
+location = "test_files/service_owners.csv"
+
+[inputs.deploys]
+type = "Csv"
+location = "test_files/deploys.csv"
+
+[pipelines.latest_deploys]
+input = "deploys"
+operations = [
+  { type = "Rename", mappings = [ { old_name = "deployed_at", new_name = "timestamp" } ] }
+]
+
+[[operations]]
+type = "Join"
+right = "owners"
+left_on = ["service_name"]
+right_on = ["service_name"]
+how = "Left"
+
+[[operations]]
+type = "Join"
+right = "latest_deploys"
+left_on = ["service_name"]
+right_on = ["service_name"]
+how = "Left"
+suffix = "_deploy"
+
+[[operations]]
+type = "GroupBy"
+columns = ["service_name", "team", "version", "timestamp_deploy"]
+aggregate = [ { column = "request_id", function = "COUNT", alias = "request_count" } ]
+
+[[operations]]
+type = "Sort"
+column = "service_name"
+order = "asc"
+"#,
+    );
+    let row = |service: &str, column: &str| {
+        let services = df.column("service_name").unwrap().str().unwrap();
+        let index = services
+            .into_iter()
+            .position(|name| name == Some(service))
+            .unwrap_or_else(|| panic!("no {service} row"));
+        df.column(column).unwrap().get(index).unwrap().to_string()
+    };
+    assert_eq!(row("api-gateway", "team"), r#""edge""#);
+    assert_eq!(row("api-gateway", "version"), r#""1.14.2""#);
+    // both sides have a timestamp, the deploy one gets the suffix
+    assert_eq!(
+        row("api-gateway", "timestamp_deploy"),
+        r#""2023-03-30T17:00:00-07:00""#
+    );
+    assert_eq!(row("user-service", "team"), r#""identity""#);
+    assert_eq!(row("user-service", "version"), "null");
+}
+
+#[test]
+fn concat_diagonal_inputs() {
+    let df = test_utils::collect_logs(
+        r#"
+[inputs.checkout]
+type = "JsonLines"
+location = "test_files/checkout_logs.jsonl"
+
+[[operations]]
+type = "Select"
+columns = ["timestamp", "service_name", "endpoint", "status_code", "response_time_ms"]
+
+[[operations]]
+type = "Concat"
+inputs = ["checkout"]
+how = "Diagonal"
+
+[[operations]]
+type = "GroupBy"
+columns = ["service_name"]
+aggregate = [
+  { column = "endpoint", function = "COUNT", alias = "rows" },
+  { column = "cart_items", function = "COUNT", alias = "carts" },
+  { column = "response_time_ms", function = "MEAN" }
+]
+"#,
+    );
+    let logs = test_utils::collect_logs(
+        r#"
+[[operations]]
+type = "Select"
+columns = ["endpoint"]
+"#,
+    );
+    let rows = df.column("rows").unwrap().cast(&DataType::UInt64).unwrap();
+    assert_eq!(rows.u64().unwrap().sum(), Some(logs.height() as u64 + 3));
+
+    let checkout = df
+        .column("service_name")
+        .unwrap()
+        .str()
+        .unwrap()
+        .equal("checkout-service");
+    let checkout = df.filter(&checkout).unwrap();
+    assert_eq!(checkout.height(), 1);
+    assert_eq!(
+        checkout.column("carts").unwrap().get(0).unwrap(),
+        AnyValue::UInt32(3)
+    );
+    // cart_items only exists in the checkout logs, every other service gets nulls
+    let carts = df.column("carts").unwrap().cast(&DataType::UInt64).unwrap();
+    assert_eq!(carts.u64().unwrap().sum(), Some(3));
+}
@@ -0,0 +1,89 @@
+use polars::prelude::*;
+use polars_cli::{configs::input::BatchConfig, follow::Batches, listen::Listener};
+use std::{
+    io::Write,
+    net::{TcpStream, UdpSocket},
+};
+
+mod test_utils;
+
+fn collect_batch(input: &str, lines: &[u8]) -> DataFrame {
+    let config = test_utils::parse_config_str(input);
+    Batches::new(&config)
+        .unwrap()
+        .dataframe(None, lines)
+        .unwrap()
+        .collect()
+        .unwrap()
+}
+
+#[test]
+fn tcp_json_lines_are_batched_by_count() {
+    let listener = Listener::bind("tcp://127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr()).unwrap();
+    client
+        .write_all(
+            b"{\"endpoint\": \"/cart\", \"status_code\": 200}\r\n\n\
+              {\"endpoint\": \"/pay\", \"status_code\": 502}\n\
+              {\"endpoint\": \"/cart\", \"status_code\": 503}\n",
+        )
+        .unwrap();
+    let batch = BatchConfig {
+        max_records: 2,
+        max_wait_ms: 5_000,
+    };
+    let first = listener.next_batch(&batch).unwrap();
+    let second = listener.next_batch(&batch).unwrap();
+    assert_eq!(first.iter().filter(|byte| **byte == b'\n').count(), 2);
+
+    let df = collect_batch(
+        r#"
+[input]
+type = "JsonLines"
+location = "tcp://127.0.0.1:0"
+"#,
+        &second,
+    );
+    assert_eq!(df.height(), 1);
+    assert_eq!(
+        df.column("status_code").unwrap().i64().unwrap().get(0),
+        Some(503)
+    );
+}
+
+#[test]
+fn udp_syslog_batch_closes_after_wait() {
+    let listener = Listener::bind("udp://127.0.0.1:0").unwrap();
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for message in [
+        "<165>1 2024-04-01T10:00:01.003Z web-1 checkout 4211 ID47 [exampleSDID@32473 iut=\"3\"] cart loaded",
+        "<11>1 2024-04-01T10:00:02Z web-1 checkout - - - payment provider timed out",
+    ] {
+        client
+            .send_to(message.as_bytes(), listener.local_addr())
+            .unwrap();
+    }
+    let batch = BatchConfig {
+        max_records: 100,
+        max_wait_ms: 200,
+    };
+    let lines = listener.next_batch(&batch).unwrap();
+
+    let df = collect_batch(
+        r#"
+[input]
+type = "Syslog"
+location = "udp://127.0.0.1:0"
+"#,
+        &lines,
+    );
+    assert_eq!(df.height(), 2);
+    let severity = df.column("severity").unwrap().i64().unwrap();
+    assert_eq!(severity.get(0), Some(5));
+    assert_eq!(severity.get(1), Some(3));
+    let facility = df.column("facility").unwrap().i64().unwrap();
+    assert_eq!(facility.get(0), Some(20));
+    assert_eq!(df.column("procid").unwrap().null_count(), 1);
+    let messages = df.column("message").unwrap().str().unwrap();
+    assert_eq!(messages.get(1), Some("payment provider timed out"));
+}
@@ -24,6 +24,19 @@ pub fn setup_test_logs() -> String {
     logs_path.to_str().unwrap().to_string()
 }
 
+/// Run a config over the test logs and collect the result, for tests that check the rows.
+#[allow(dead_code)]
+pub fn collect_logs(config: &str) -> polars::prelude::DataFrame {
+    let input = setup_test_logs();
+    let mut config = polars_cli::configs::parse::parse_config(config);
+    config.input = Some(polars_cli::configs::input::InputConfig::new(
+        &input, "jsonl", false, false,
+    ));
+    polars_cli::runner::run(&config)
+        .and_then(|df| df.collect().map_err(polars_cli::runner::RunnerError::from))
+        .expect("pipeline should run")
+}
+
 #[macro_export]
 macro_rules! config_string_test {
     ($test_name:ident, $config:expr) => {